@@ -0,0 +1,184 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::widgets::WidgetEntry;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map linear progress (0..=1) onto the eased curve (also 0..=1)
+    pub fn apply(&self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    -1. + (4. - 2. * t) * t
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// How recalling a Preset transitions from the current values
+pub struct CrossfadeOptions {
+    /// Zero means "snap" straight to the Preset values
+    pub duration_ms: u64,
+    pub easing: Easing,
+    /// How many times per second intermediate values are published
+    pub rate_hz: f64,
+}
+
+impl Default for CrossfadeOptions {
+    fn default() -> Self {
+        CrossfadeOptions {
+            duration_ms: 0,
+            easing: Easing::Linear,
+            rate_hz: 30.,
+        }
+    }
+}
+
+/// A crossfade in progress, from the values at the time it started
+/// towards the values of a Preset
+pub struct Crossfade {
+    pub preset_name: String,
+    from: BTreeMap<u64, Value>,
+    to: BTreeMap<u64, Value>,
+    pub publish: bool,
+    easing: Easing,
+    duration: Duration,
+    step_interval: Duration,
+    started: Instant,
+    last_step: Option<Instant>,
+}
+
+impl Crossfade {
+    pub fn new(
+        preset_name: &str,
+        from: BTreeMap<u64, Value>,
+        to: BTreeMap<u64, Value>,
+        publish: bool,
+        options: &CrossfadeOptions,
+    ) -> Self {
+        Crossfade {
+            preset_name: String::from(preset_name),
+            from,
+            to,
+            publish,
+            easing: options.easing,
+            duration: Duration::from_millis(options.duration_ms),
+            step_interval: Duration::from_secs_f64(1.0 / options.rate_hz.max(1.0)),
+            started: Instant::now(),
+            last_step: None,
+        }
+    }
+
+    /// Linear progress, 0..=1
+    pub fn progress(&self) -> f64 {
+        if self.duration.is_zero() {
+            1.0
+        } else {
+            (self.started.elapsed().as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.progress() >= 1.0
+    }
+
+    /// Time until the next step is due
+    pub fn until_next_step(&self) -> Duration {
+        match self.last_step {
+            Some(last) => self.step_interval.saturating_sub(last.elapsed()),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Interpolated values for every Widget in the target Preset, if a step is due.
+    /// Values which cannot be interpolated (e.g. booleans) only change on the final step.
+    pub fn step(&mut self, widgets: &[WidgetEntry]) -> Option<Vec<(u64, Value)>> {
+        if !self.is_finished() && !self.until_next_step().is_zero() {
+            return None;
+        }
+        self.last_step = Some(Instant::now());
+        let t = self.easing.apply(self.progress());
+        Some(
+            widgets
+                .iter()
+                .filter_map(|widget| {
+                    let id = widget.common().id;
+                    let to = self.to.get(&id)?;
+                    let value = match self.from.get(&id) {
+                        // Options have no order, so there is nothing in between
+                        Some(_) if matches!(widget, WidgetEntry::Choice(_)) => {
+                            if t < 1.0 {
+                                return None;
+                            }
+                            to.clone()
+                        }
+                        Some(from) => interpolate(from, to, t, should_round(widget))?,
+                        None => to.clone(),
+                    };
+                    Some((id, value))
+                })
+                .collect(),
+        )
+    }
+}
+
+fn should_round(widget: &WidgetEntry) -> bool {
+    match widget {
+        WidgetEntry::FloatNumber(e) | WidgetEntry::WholeNumber(e) => e.should_round(),
+        WidgetEntry::NumberArray(e) => e.should_round(),
+        _ => false,
+    }
+}
+
+/// Numbers, and arrays of numbers (e.g. colours and points), are interpolated
+/// element by element; integers stay integers. Anything else snaps at the end.
+fn interpolate(from: &Value, to: &Value, t: f64, round: bool) -> Option<Value> {
+    match (from, to) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a_f, b_f) = (a.as_f64()?, b.as_f64()?);
+            let v = a_f + (b_f - a_f) * t;
+            if round {
+                // e.g. whole numbers, which are still stored as floats
+                Some(Value::from(v.round()))
+            } else if b.is_f64() {
+                Some(Value::from(v))
+            } else {
+                Some(Value::from(v.round() as i64))
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => a
+            .iter()
+            .zip(b.iter())
+            .map(|(x, y)| interpolate(x, y, t, round))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::Array),
+        _ => {
+            if t >= 1.0 {
+                Some(to.clone())
+            } else {
+                None
+            }
+        }
+    }
+}
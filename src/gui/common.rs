@@ -1,10 +1,15 @@
 use std::fs;
 
 use anyhow::anyhow;
-use egui::{Color32, DragValue, RichText, Ui};
+use egui::{Color32, DragValue, RichText, TextEdit, Ui};
 use log::*;
 
-use crate::{project::try_load, Model};
+use crate::{
+    model::QueueItem,
+    project::{try_load, BroadcastOrder, Page, Project},
+    widgets::Common,
+    Model,
+};
 
 use super::tether_gui_utils::EditableTetherSettings;
 
@@ -12,11 +17,117 @@ pub fn standard_spacer(ui: &mut egui::Ui) {
     ui.add_space(16.);
 }
 
+fn perform_mode_id() -> egui::Id {
+    egui::Id::new("perform_mode")
+}
+
+/// Perform mode is also kept in egui's memory, so that Widgets (which only
+/// get a Ui to render into) can hide their own editing controls
+pub fn set_perform_mode(ctx: &egui::Context, locked: bool) {
+    ctx.data_mut(|d| d.insert_temp(perform_mode_id(), locked));
+}
+
+pub fn is_perform_mode(ctx: &egui::Context) -> bool {
+    ctx.data_mut(|d| d.get_temp(perform_mode_id()).unwrap_or(false))
+}
+
+/// Lock (optionally setting a PIN) while editing; otherwise the PIN entry
+/// needed to unlock
+pub fn perform_mode_controls(ui: &mut Ui, model: &mut Model) {
+    if model.is_locked {
+        ui.label(RichText::new("🔒 Perform mode").color(Color32::YELLOW));
+        let response = ui.add(
+            TextEdit::singleline(&mut model.unlock_attempt)
+                .password(true)
+                .hint_text("PIN")
+                .desired_width(64.),
+        );
+        if ui.button("Unlock").clicked()
+            || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+        {
+            model.try_unlock();
+        }
+        if model.unlock_failed {
+            ui.label(RichText::new("Wrong PIN").color(Color32::RED));
+        }
+    } else {
+        ui.menu_button("🔒 Perform mode", |ui| {
+            ui.label("PIN to unlock (optional)");
+            ui.add(TextEdit::singleline(&mut model.project.perform_pin).password(true));
+            ui.small("Saved with the project");
+            if ui
+                .button("Lock")
+                .on_hover_text("Hide all editing until unlocked")
+                .clicked()
+            {
+                model.lock();
+                ui.close_menu();
+            }
+        });
+    }
+}
+
 pub fn common_remove_button(ui: &mut Ui) -> bool {
     ui.button("❌ Remove").clicked()
 }
 
+/// Actions available for any Widget in edit mode
+pub fn common_edit_actions(
+    ui: &mut Ui,
+    index: usize,
+    common: &mut Common,
+    pages: &[Page],
+    queue: &mut Vec<QueueItem>,
+) {
+    ui.horizontal(|ui| {
+        ui.menu_button("Move to…", |ui| {
+            for page in pages {
+                ui.menu_button(&page.name, |ui| {
+                    if ui.button("(No group)").clicked() {
+                        common.page_id = page.id;
+                        common.group_id = None;
+                        ui.close_menu();
+                    }
+                    for group in &page.groups {
+                        if ui.button(&group.name).clicked() {
+                            common.page_id = page.id;
+                            common.group_id = Some(group.id);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+        });
+        if ui.button("⎘ Duplicate").clicked() {
+            queue.push(QueueItem::Duplicate(index));
+        }
+        if ui
+            .button("📋 Copy")
+            .on_hover_text("Copy to clipboard as JSON")
+            .clicked()
+        {
+            queue.push(QueueItem::Copy(vec![index]));
+        }
+        if common_remove_button(ui) {
+            queue.push(QueueItem::Remove(index));
+        }
+    });
+}
+
 pub fn general_agent_area(ui: &mut Ui, model: &mut Model) {
+    if model.is_locked {
+        ui.heading("Project");
+        ui.small(
+            model
+                .json_file
+                .as_deref()
+                .unwrap_or("(No JSON file loaded)"),
+        );
+        standard_spacer(ui);
+        connection_status(ui, model);
+        return;
+    }
+
     ui.heading("Load/Save Project");
     if let Some(json_path) = &model.json_file {
         ui.small(json_path);
@@ -47,20 +158,62 @@ pub fn general_agent_area(ui: &mut Ui, model: &mut Model) {
                 .pick_file()
             {
                 let path_string = path.display().to_string();
-                let (project, loaded) = try_load(&path_string);
-                if loaded {
-                    info!("Loaded project file OK");
-                    model.json_file = Some(path_string);
-                    model.project = project;
-                    model.attempt_new_tether_connection();
+                match try_load(&path_string) {
+                    Ok(Some(project)) => {
+                        info!("Loaded project file OK");
+                        model.json_file = Some(path_string);
+                        model.project = project;
+                        model.history.reset();
+                        model.restore_layout();
+                        model.project_error = None;
+                        model.attempt_new_tether_connection();
+                        model.queue_broadcast_on_load();
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!("Failed to load project: {}", e);
+                        model.project_error = Some(e.to_string());
+                    }
                 }
             }
         }
         if ui.button("New").clicked() {
             model.project.widgets.clear();
+            model.project.presets.clear();
+            model.project.pages = Project::default().pages;
+            model.project.active_page = 0;
             model.json_file = None;
         }
     });
+    if let Some(e) = &model.project_error {
+        ui.label(RichText::new(e).color(Color32::RED));
+        if ui.button("Dismiss").clicked() {
+            model.project_error = None;
+        }
+    }
+
+    ui.collapsing("Broadcast on load", |ui| {
+        let options = &mut model.project.broadcast_on_load;
+        ui.checkbox(&mut options.enabled, "Broadcast all values on load")
+            .on_hover_text("Publish the value of every widget once connected");
+        ui.horizontal(|ui| {
+            ui.label("Order");
+            ui.radio_value(&mut options.order, BroadcastOrder::ProjectOrder, "Project");
+            ui.radio_value(
+                &mut options.order,
+                BroadcastOrder::ReverseProjectOrder,
+                "Reverse",
+            );
+            ui.radio_value(&mut options.order, BroadcastOrder::ByName, "By name");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Delay (ms)");
+            ui.add(DragValue::new(&mut options.delay_ms));
+        });
+        if ui.button("Broadcast now").clicked() {
+            model.queue_broadcast();
+        }
+    });
 
     standard_spacer(ui);
     ui.separator();
@@ -114,6 +267,10 @@ pub fn general_agent_area(ui: &mut Ui, model: &mut Model) {
         }
     }
 
+    connection_status(ui, model);
+}
+
+fn connection_status(ui: &mut Ui, model: &mut Model) {
     if model.tether_agent.is_connected() {
         ui.label(RichText::new("Connected ☑").color(Color32::GREEN));
     } else {
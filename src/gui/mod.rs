@@ -1,11 +1,15 @@
 use crate::model::{ActiveView, Model};
 
 use self::{
-    common::{general_agent_area, standard_spacer},
+    common::{general_agent_area, perform_mode_controls, set_perform_mode, standard_spacer},
+    pages_view::pages_bar,
+    presets_view::presets_bar,
     widget_view::{available_widgets, widgets_in_use},
 };
 
 pub mod common;
+pub mod pages_view;
+pub mod presets_view;
 pub mod tether_gui_utils;
 pub mod utilities_view;
 pub mod widget_view;
@@ -19,6 +23,8 @@ fn common_left_panel(ctx: &egui::Context, model: &mut Model) {
 }
 
 pub fn render(ctx: &egui::Context, model: &mut Model) {
+    set_perform_mode(ctx, model.is_locked);
+
     egui::TopBottomPanel::top("Tabs")
         .min_height(32.)
         .show(ctx, |ui| {
@@ -35,23 +41,50 @@ pub fn render(ctx: &egui::Context, model: &mut Model) {
                 {
                     model.continuous_mode = true;
                 };
+                ui.separator();
+                perform_mode_controls(ui, model);
+                if model.is_locked {
+                    return;
+                }
+                ui.separator();
+                if ui
+                    .add_enabled(model.history.can_undo(), egui::Button::new("⟲ Undo"))
+                    .on_hover_text("Ctrl+Z")
+                    .clicked()
+                {
+                    model.undo();
+                }
+                if ui
+                    .add_enabled(model.history.can_redo(), egui::Button::new("⟳ Redo"))
+                    .on_hover_text("Ctrl+Shift+Z")
+                    .clicked()
+                {
+                    model.redo();
+                }
             })
         });
 
     match model.active_window {
         ActiveView::WidgetView => {
             common_left_panel(ctx, model);
-            egui::SidePanel::right("Available Widgets")
-                .min_width(128.)
-                .show(ctx, |ui| {
-                    ui.heading("Available Widgets");
+            if !model.is_locked {
+                egui::SidePanel::right("Available Widgets")
+                    .min_width(128.)
+                    .show(ctx, |ui| {
+                        ui.heading("Available Widgets");
 
-                    standard_spacer(ui);
+                        standard_spacer(ui);
 
-                    available_widgets(ui, model);
-                });
+                        available_widgets(ui, model);
+                    });
+            }
+
+            egui::TopBottomPanel::bottom("Presets").show(ctx, |ui| {
+                presets_bar(ui, model);
+            });
 
             egui::CentralPanel::default().show(ctx, |ui| {
+                pages_bar(ui, model);
                 widgets_in_use(ctx, ui, model);
             });
         }
@@ -0,0 +1,136 @@
+use egui::Ui;
+
+use crate::Model;
+
+/// Space between the edges of the central area and arranged windows
+const GRID_MARGIN: f32 = 8.;
+
+/// Tabs for switching between Pages, and toggles for showing or hiding
+/// each Group on the active Page; right-click either to rename or remove
+pub fn pages_bar(ui: &mut Ui, model: &mut Model) {
+    // In perform mode, Pages can still be switched but not edited, and Groups
+    // stay shown or hidden as they were, since that is saved in the project
+    let locked = model.is_locked;
+    let project = &mut model.project;
+    let mut arrange = false;
+    let mut layout_changed = false;
+
+    ui.horizontal_wrapped(|ui| {
+        let can_remove = project.pages.len() > 1;
+        let mut to_remove = None;
+        for (i, page) in project.pages.iter_mut().enumerate() {
+            let response = ui.selectable_value(&mut project.active_page, i, &page.name);
+            if !locked {
+                response.context_menu(|ui| {
+                    ui.text_edit_singleline(&mut page.name);
+                    if ui
+                        .add_enabled(can_remove, egui::Button::new("❌ Remove page"))
+                        .on_hover_text("Widgets on this page move to the first page")
+                        .clicked()
+                    {
+                        to_remove = Some(i);
+                        ui.close_menu();
+                    }
+                });
+            }
+        }
+        if let Some(index) = to_remove {
+            project.remove_page(index);
+        }
+        if locked {
+            return;
+        }
+        if ui.button("＋ Page").clicked() {
+            project.add_page(&format!("Page {}", project.pages.len() + 1));
+        }
+        ui.separator();
+        ui.menu_button("Layout", |ui| {
+            if ui
+                .button("Auto-arrange in grid")
+                .on_hover_text("Arrange the widgets on this page in rows, group by group")
+                .clicked()
+            {
+                arrange = true;
+                ui.close_menu();
+            }
+            if ui
+                .button("Reset layout")
+                .on_hover_text("Forget window positions and sizes on this page")
+                .clicked()
+            {
+                project.reset_layout();
+                layout_changed = true;
+                ui.close_menu();
+            }
+        });
+    });
+
+    let counts: Vec<usize> = match project.pages.get(project.active_page) {
+        Some(page) => page
+            .groups
+            .iter()
+            .map(|g| {
+                project
+                    .widgets
+                    .iter()
+                    .filter(|w| project.group_of(w.common()).map(|wg| wg.id) == Some(g.id))
+                    .count()
+            })
+            .collect(),
+        None => return,
+    };
+
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Groups");
+        let mut to_remove = None;
+        let page = &mut project.pages[project.active_page];
+        for (group, count) in page.groups.iter_mut().zip(counts) {
+            let label = format!(
+                "{} {} ({})",
+                if group.collapsed { "⏵" } else { "⏷" },
+                group.name,
+                count
+            );
+            let response = ui
+                .add_enabled(!locked, egui::SelectableLabel::new(!group.collapsed, label))
+                .on_hover_text("Show or hide the widgets in this group");
+            if response.clicked() {
+                group.collapsed = !group.collapsed;
+            }
+            if locked {
+                continue;
+            }
+            response.context_menu(|ui| {
+                ui.text_edit_singleline(&mut group.name);
+                if ui
+                    .button("❌ Remove group")
+                    .on_hover_text("Widgets in this group stay on the page")
+                    .clicked()
+                {
+                    to_remove = Some(group.id);
+                    ui.close_menu();
+                }
+            });
+        }
+        let group_count = page.groups.len();
+        if let Some(id) = to_remove {
+            project.remove_group(id);
+        }
+        if !locked && ui.button("＋ Group").clicked() {
+            project.add_group(&format!("Group {}", group_count + 1));
+        }
+    });
+    ui.separator();
+
+    if arrange {
+        let area = ui.ctx().available_rect();
+        project.arrange_in_grid(
+            [area.min.x + GRID_MARGIN, ui.cursor().min.y + GRID_MARGIN],
+            area.width() - 2. * GRID_MARGIN,
+        );
+        layout_changed = true;
+    }
+    if layout_changed {
+        model.restore_layout();
+    }
+}
@@ -0,0 +1,124 @@
+use egui::{Color32, DragValue, ProgressBar, RichText, Ui};
+
+use crate::{crossfade::Easing, midi_mapping::MidiMapping, Model};
+
+/// One button per Preset for quick recall, plus saving the current values as a new Preset
+pub fn presets_bar(ui: &mut Ui, model: &mut Model) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Presets");
+        let mut to_recall = None;
+        for (i, preset) in model.project.presets.iter().enumerate() {
+            if ui.button(&preset.name).clicked() {
+                to_recall = Some(i);
+            }
+        }
+        if let Some(index) = to_recall {
+            model.recall_preset(index);
+        }
+
+        if model.is_locked {
+            return;
+        }
+        ui.separator();
+        ui.text_edit_singleline(&mut model.new_preset_name);
+        if ui.button("＋ Save preset").clicked() {
+            model.project.save_preset(&model.new_preset_name);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let options = &mut model.project.crossfade;
+        ui.label("Crossfade (ms)")
+            .on_hover_text("Zero to recall presets immediately");
+        ui.add(DragValue::new(&mut options.duration_ms).speed(10.0));
+        ui.add_enabled_ui(options.duration_ms > 0, |ui| {
+            egui::ComboBox::from_id_source("crossfade_easing")
+                .selected_text(format!("{:?}", options.easing))
+                .show_ui(ui, |ui| {
+                    for easing in [
+                        Easing::Linear,
+                        Easing::EaseIn,
+                        Easing::EaseOut,
+                        Easing::EaseInOut,
+                    ] {
+                        ui.selectable_value(&mut options.easing, easing, format!("{:?}", easing));
+                    }
+                });
+            ui.label("Rate (Hz)");
+            ui.add(
+                DragValue::new(&mut options.rate_hz)
+                    .clamp_range(1.0..=120.0)
+                    .speed(1.0),
+            );
+        });
+        if let Some(crossfade) = &model.crossfade {
+            ui.add(
+                ProgressBar::new(crossfade.progress() as f32)
+                    .text(format!("→ {}", crossfade.preset_name)),
+            );
+            if ui.button("⏹ Stop").clicked() {
+                model.crossfade = None;
+            }
+        }
+    });
+
+    if model.is_locked {
+        return;
+    }
+    ui.collapsing("Manage presets", |ui| {
+        manage_presets(ui, model);
+    });
+}
+
+fn manage_presets(ui: &mut Ui, model: &mut Model) {
+    if model.project.presets.is_empty() {
+        ui.small("No presets saved yet");
+        return;
+    }
+    let current_values = model.project.current_values();
+    let mut to_delete = None;
+    egui::Grid::new("presets")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, preset) in model.project.presets.iter_mut().enumerate() {
+                ui.text_edit_singleline(&mut preset.name)
+                    .on_hover_text("Rename");
+                if ui
+                    .button("Overwrite")
+                    .on_hover_text("Replace stored values with the current widget values")
+                    .clicked()
+                {
+                    preset.values = current_values.clone();
+                }
+                ui.checkbox(&mut preset.publish_on_recall, "Publish on recall");
+                ui.horizontal(|ui| {
+                    if ui.button("Learn MIDI").clicked() {
+                        preset.midi_mapping = Some(MidiMapping::Learning);
+                    }
+                    match &preset.midi_mapping {
+                        Some(MidiMapping::Learning) => {
+                            ui.label("Learning...");
+                        }
+                        Some(MidiMapping::Set(mapping)) => {
+                            ui.label(format!(
+                                "ch {} note {}",
+                                mapping.channel, mapping.controller_or_note
+                            ));
+                        }
+                        None => {}
+                    }
+                });
+                if ui
+                    .button(RichText::new("❌ Delete").color(Color32::LIGHT_RED))
+                    .clicked()
+                {
+                    to_delete = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(index) = to_delete {
+        model.project.presets.remove(index);
+    }
+}
@@ -1,19 +1,34 @@
-use egui::{Color32, Response, RichText, Ui};
-use log::{debug, error};
+use egui::{Color32, InnerResponse, Response, RichText, Ui, Vec2};
+use log::{debug, error, warn};
 use serde::Serialize;
-use tether_agent::{PlugOptionsBuilder, TetherAgent};
+use serde_json::Value;
+use tether_agent::TetherAgent;
 
 use crate::{
     midi_mapping::MidiMapping,
     model::QueueItem,
+    payload::{NumberType, PayloadEncoding, PayloadFormat, PayloadShape},
     widgets::{
-        boolean::BoolWidget, colours::ColourWidget, empty::EmptyWidget, generic::GenericJSONWidget,
-        numbers::NumberWidget, point::Point2DWidget, CustomWidget, Qos, View, WidgetEntry,
+        boolean::BoolWidget,
+        choice::ChoiceWidget,
+        colours::ColourWidget,
+        empty::EmptyWidget,
+        generic::GenericJSONWidget,
+        gradient::GradientWidget,
+        monitors::{MonitorDisplay, MonitorWidget},
+        number_array::NumberArrayWidget,
+        numbers::{NumberSummary, NumberWidget},
+        point::Point2DWidget,
+        point3d::Point3DWidget,
+        string::StringWidget,
+        tracking::TrackingWidget,
+        Common, CustomWidget, Qos, RepeatOptions, WidgetEntry, WindowLayout,
+        MIN_REPEAT_INTERVAL_MS,
     },
     Model,
 };
 
-use super::common::{common_remove_button, standard_spacer};
+use super::common::{common_edit_actions, is_perform_mode, standard_spacer};
 
 pub fn common_in_use_heading<T: Serialize>(ui: &mut egui::Ui, entry: &mut impl CustomWidget<T>) {
     ui.horizontal(|ui| {
@@ -22,7 +37,7 @@ pub fn common_in_use_heading<T: Serialize>(ui: &mut egui::Ui, entry: &mut impl C
                 .color(Color32::WHITE)
                 .size(18.),
         );
-        if ui.button("edit").clicked() {
+        if !is_perform_mode(ui.ctx()) && ui.button("edit").clicked() {
             entry.common_mut().set_edit_mode(true);
         }
     });
@@ -37,6 +52,7 @@ pub fn common_save_button<T: Serialize>(
 ) {
     if ui.button("Save").clicked() {
         update_plug_definition(entry, tether_agent);
+        entry.common_mut().update_sync_plug(tether_agent);
         entry.common_mut().set_edit_mode(false);
     }
 }
@@ -54,11 +70,57 @@ pub fn common_send_button<T: Serialize>(
         }
         res
     });
+    common_repeat_controls(ui, entry.common_mut());
     res.inner
 }
 
+/// Start/stop and send count, if the Widget is set up to repeat
+pub fn common_repeat_controls(ui: &mut egui::Ui, common: &mut Common) {
+    let repeat = match common.repeat {
+        Some(repeat) => repeat,
+        None => return,
+    };
+    ui.horizontal(|ui| {
+        if repeat.is_running {
+            if ui.button("⏹ Stop").clicked() {
+                common.stop_repeat();
+            }
+        } else if ui.button("▶ Repeat").clicked() {
+            common.start_repeat();
+        }
+        ui.label(format!("every {} ms", repeat.interval_ms));
+        ui.label(
+            RichText::new(format!("Sent: {}", common.repeat_count())).color(Color32::LIGHT_GREEN),
+        );
+        let failures = common.repeat_failures();
+        if failures > 0 {
+            ui.label(RichText::new(format!("Failed: {}", failures)).color(Color32::LIGHT_RED))
+                .on_hover_text("Sends which were due while the repeat sender was not connected");
+        }
+    });
+}
+
 pub fn common_send<T: Serialize>(entry: &mut impl CustomWidget<T>, tether_agent: &TetherAgent) {
-    match tether_agent.encode_and_publish(&entry.common().plug, entry.value()) {
+    publish_value(entry.common(), entry.value(), tether_agent);
+}
+
+/// Publish any value on the Widget's plug, using its payload encoding
+pub fn publish_value<T: Serialize>(common: &Common, value: &T, tether_agent: &TetherAgent) {
+    let payload = match common.encoding.encode(value) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Failed to encode payload: {}", e);
+            return;
+        }
+    };
+    let plug = match &common.plug {
+        Some(plug) => plug,
+        None => {
+            warn!("Widget \"{}\" has no output plug", common.name);
+            return;
+        }
+    };
+    match tether_agent.publish(plug, Some(&payload)) {
         Ok(()) => debug!("Send OK"),
         Err(_) => error!(
             "Failed to send via Tether; connected? {}",
@@ -67,114 +129,157 @@ pub fn common_send<T: Serialize>(entry: &mut impl CustomWidget<T>, tether_agent:
     }
 }
 
+/// Publish the current value of any Widget, whatever its type
+pub fn send_entry(entry: &mut WidgetEntry, tether_agent: &TetherAgent) {
+    match entry {
+        WidgetEntry::FloatNumber(e) => common_send(e, tether_agent),
+        WidgetEntry::WholeNumber(e) => common_send(e, tether_agent),
+        WidgetEntry::NumberArray(e) => e.send(tether_agent),
+        WidgetEntry::Colour(e) => e.send(tether_agent),
+        WidgetEntry::Gradient(e) => e.send(tether_agent),
+        WidgetEntry::Bool(e) => common_send(e, tether_agent),
+        WidgetEntry::Empty(e) => common_send(e, tether_agent),
+        WidgetEntry::Point2D(e) => e.send(tether_agent),
+        WidgetEntry::Point3D(e) => common_send(e, tether_agent),
+        WidgetEntry::Tracking(e) => common_send(e, tether_agent),
+        WidgetEntry::Generic(e) => e.publish_from_json_string(tether_agent),
+        WidgetEntry::String(e) => common_send(e, tether_agent),
+        WidgetEntry::Choice(e) => common_send(e, tether_agent),
+        // Display-only
+        WidgetEntry::Monitor(_) => {}
+    }
+}
+
+/// The current value of any Widget, encoded as `send_entry` would publish
+/// it; None for display-only Widgets, or if it cannot be encoded
+pub fn entry_payload(entry: &WidgetEntry) -> Option<Vec<u8>> {
+    let value = match entry {
+        WidgetEntry::NumberArray(e) => e.output(),
+        WidgetEntry::Colour(e) => e.output(),
+        WidgetEntry::Gradient(e) => e.output(),
+        WidgetEntry::Point2D(e) => e.output(),
+        WidgetEntry::Generic(e) => serde_json::from_str(e.value()).ok()?,
+        WidgetEntry::Empty(_) => Value::Null,
+        WidgetEntry::Monitor(_) => return None,
+        _ => entry.value_json()?,
+    };
+    match entry.common().encoding.encode(&value) {
+        Ok(payload) => Some(payload),
+        Err(e) => {
+            error!("Failed to encode payload: {}", e);
+            None
+        }
+    }
+}
+
 pub fn entry_topic<T: Serialize>(ui: &mut egui::Ui, entry: &impl CustomWidget<T>) {
     ui.label(
-        RichText::new(format!("Topic: {}", entry.common().plug.topic())).color(Color32::LIGHT_BLUE),
+        RichText::new(format!(
+            "Topic: {}",
+            entry.common().plug.as_ref().map_or("(none)", |p| p.topic())
+        ))
+        .color(Color32::LIGHT_BLUE),
     );
 }
 
-pub fn widgets_in_use(ctx: &egui::Context, ui: &mut Ui, model: &mut Model) {
-    let widgets = &mut model.project.widgets;
-
-    for (i, entry) in widgets.iter_mut().enumerate() {
-        match entry {
-            WidgetEntry::FloatNumber(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
-            }
-            WidgetEntry::WholeNumber(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
-            }
-            WidgetEntry::Colour(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
-            }
-            WidgetEntry::Bool(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
-            }
-            WidgetEntry::Empty(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
-            }
-            WidgetEntry::Point2D(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
+/// Based on the Widget ID rather than its index, so that e.g. window
+/// positions are kept when other Widgets are removed or reordered
+pub fn widget_window_id(common: &Common, layout_generation: u64) -> egui::Id {
+    egui::Id::new(("widget", common.id, layout_generation))
+}
+
+/// A window for the Widget, initially placed according to the stored layout;
+/// windows cannot be moved in perform mode
+fn widget_window(common: &Common, layout_generation: u64, locked: bool) -> egui::Window<'static> {
+    let window = egui::Window::new(&common.name)
+        .id(widget_window_id(common, layout_generation))
+        .movable(!locked);
+    match &common.window {
+        Some(layout) => window
+            .default_pos([layout.x, layout.y])
+            .default_size([layout.width, layout.height])
+            .default_open(!layout.collapsed),
+        None => window,
+    }
+}
+
+/// Keep the stored layout up to date with the window as shown; the
+/// content size is only known while the window is not collapsed
+fn store_window_layout(common: &mut Common, response: Option<InnerResponse<Option<Vec2>>>) {
+    if let Some(response) = response {
+        let rect = response.response.rect;
+        let size = match response.inner {
+            Some(size) => size,
+            None => match &common.window {
+                Some(previous) => Vec2::new(previous.width, previous.height),
+                None => Vec2::new(rect.width(), 0.),
+            },
+        };
+        common.window = Some(WindowLayout {
+            x: rect.min.x,
+            y: rect.min.y,
+            width: size.x,
+            height: size.y,
+            collapsed: response.inner.is_none(),
+        });
+    }
+}
+
+/// The window for any Widget: its editor (with the common edit actions) or
+/// its in-use view, keeping the stored layout up to date
+fn show_widget_window(ctx: &egui::Context, model: &mut Model, index: usize) {
+    let entry = &mut model.project.widgets[index];
+    let response =
+        widget_window(entry.common(), model.layout_generation, model.is_locked).show(ctx, |ui| {
+            if entry.common().is_edit_mode() {
+                entry.view_mut().render_editing(ui, &mut model.tether_agent);
+                common_edit_actions(
+                    ui,
+                    index,
+                    entry.common_mut(),
+                    &model.project.pages,
+                    &mut model.queue,
+                );
+            } else {
+                entry.view_mut().render_in_use(ui, &model.tether_agent);
             }
-            WidgetEntry::Generic(e) => {
-                egui::Window::new(&e.common().name)
-                    .id(format!("{}", i).into())
-                    .show(ctx, |ui| {
-                        if e.common().is_edit_mode() {
-                            e.render_editing(ui, &mut model.tether_agent);
-                            if common_remove_button(ui) {
-                                model.queue.push(QueueItem::Remove(i));
-                            }
-                        } else {
-                            e.render_in_use(ui, &model.tether_agent);
-                        }
-                    });
+            ui.max_rect().size()
+        });
+    store_window_layout(entry.common_mut(), response);
+}
+
+pub fn widgets_in_use(ctx: &egui::Context, ui: &mut Ui, model: &mut Model) {
+    let visible: Vec<bool> = model
+        .project
+        .widgets
+        .iter()
+        .map(|w| model.project.is_widget_visible(w.common()))
+        .collect();
+    let numbers: Vec<NumberSummary> = model
+        .project
+        .widgets
+        .iter()
+        .filter_map(|w| match w {
+            WidgetEntry::FloatNumber(e) | WidgetEntry::WholeNumber(e) => Some(e.summary()),
+            _ => None,
+        })
+        .collect();
+    for (i, is_visible) in visible.into_iter().enumerate() {
+        if !is_visible {
+            continue;
+        }
+        if let WidgetEntry::FloatNumber(e) | WidgetEntry::WholeNumber(e) =
+            &mut model.project.widgets[i]
+        {
+            let id = e.common().id;
+            e.set_others(numbers.iter().filter(|n| n.id != id).cloned().collect());
+        }
+        show_widget_window(ctx, model, i);
+        if let WidgetEntry::FloatNumber(e) | WidgetEntry::WholeNumber(e) =
+            &mut model.project.widgets[i]
+        {
+            if let Some((id, value)) = e.take_paired_change() {
+                model.queue.push(QueueItem::SetNumber(id, value));
             }
         }
 
@@ -188,23 +293,19 @@ pub fn widgets_in_use(ctx: &egui::Context, ui: &mut Ui, model: &mut Model) {
 
 pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
     if ui.button("Boolean").clicked() {
-        model
-            .project
-            .widgets
-            .push(WidgetEntry::Bool(BoolWidget::new(
-                "Boolean Message",
-                Some("A true or false value"),
-                "booleans",
-                None,
-                false,
-                &mut model.tether_agent,
-            )));
+        model.project.add_widget(WidgetEntry::Bool(BoolWidget::new(
+            "Boolean Message",
+            Some("A true or false value"),
+            "booleans",
+            None,
+            false,
+            &mut model.tether_agent,
+        )));
     }
     if ui.button("Empty").clicked() {
         model
             .project
-            .widgets
-            .push(WidgetEntry::Empty(EmptyWidget::new(
+            .add_widget(WidgetEntry::Empty(EmptyWidget::new(
                 "Empty Meassage",
                 Some("A message with no payload"),
                 "events",
@@ -216,8 +317,7 @@ pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
     if ui.button("Floating Point").clicked() {
         model
             .project
-            .widgets
-            .push(WidgetEntry::FloatNumber(NumberWidget::new(
+            .add_widget(WidgetEntry::FloatNumber(NumberWidget::new(
                 "Floating Point Number",
                 Some("A single 64-bit floating point number"),
                 "floats",
@@ -231,8 +331,7 @@ pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
     if ui.button("Whole Number").clicked() {
         model
             .project
-            .widgets
-            .push(WidgetEntry::WholeNumber(NumberWidget::new(
+            .add_widget(WidgetEntry::WholeNumber(NumberWidget::new(
                 "Whole Number",
                 Some("A single 64-bit whole number"),
                 "numbers",
@@ -243,11 +342,24 @@ pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
                 &mut model.tether_agent,
             )));
     }
+    if ui.button("Number Array").clicked() {
+        model
+            .project
+            .add_widget(WidgetEntry::NumberArray(NumberArrayWidget::new(
+                "Number Array",
+                Some("A list of numbers, e.g. for LED strips or motors"),
+                "numberArray",
+                None,
+                8,
+                0. ..=1.,
+                false,
+                &mut model.tether_agent,
+            )));
+    }
     if ui.button("Point2D").clicked() {
         model
             .project
-            .widgets
-            .push(WidgetEntry::Point2D(Point2DWidget::new(
+            .add_widget(WidgetEntry::Point2D(Point2DWidget::new(
                 "Point2D",
                 Some("X and Y values"),
                 "point2d",
@@ -255,11 +367,32 @@ pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
                 &mut model.tether_agent,
             )));
     }
+    if ui.button("Point3D").clicked() {
+        model
+            .project
+            .add_widget(WidgetEntry::Point3D(Point3DWidget::new(
+                "Point3D",
+                Some("X, Y and Z values"),
+                "point3d",
+                None,
+                &mut model.tether_agent,
+            )));
+    }
+    if ui.button("Tracking simulator").clicked() {
+        model
+            .project
+            .add_widget(WidgetEntry::Tracking(TrackingWidget::new(
+                "Tracking Simulator",
+                Some("A set of points with IDs, moving on their own"),
+                "trackedPoints",
+                None,
+                &mut model.tether_agent,
+            )));
+    }
     if ui.button("Generic data").clicked() {
         model
             .project
-            .widgets
-            .push(WidgetEntry::Generic(GenericJSONWidget::new(
+            .add_widget(WidgetEntry::Generic(GenericJSONWidget::new(
                 "Generic JSON Data",
                 Some("Any generic data, in JSON format"),
                 "generic",
@@ -267,11 +400,33 @@ pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
                 &mut model.tether_agent,
             )));
     }
+    if ui.button("Text").clicked() {
+        model
+            .project
+            .add_widget(WidgetEntry::String(StringWidget::new(
+                "Text",
+                Some("A string of text"),
+                "text",
+                None,
+                &mut model.tether_agent,
+            )));
+    }
+    if ui.button("Choice").clicked() {
+        model
+            .project
+            .add_widget(WidgetEntry::Choice(ChoiceWidget::new(
+                "Choice",
+                Some("One of a list of options, e.g. a mode"),
+                "modes",
+                None,
+                &["idle", "attract", "play"],
+                &mut model.tether_agent,
+            )));
+    }
     if ui.button("Colour").clicked() {
         model
             .project
-            .widgets
-            .push(WidgetEntry::Colour(ColourWidget::new(
+            .add_widget(WidgetEntry::Colour(ColourWidget::new(
                 "Colour",
                 Some("8-bit colour including alpha"),
                 "colours",
@@ -279,6 +434,81 @@ pub fn available_widgets(ui: &mut egui::Ui, model: &mut Model) {
                 &mut model.tether_agent,
             )))
     }
+    if ui.button("Gradient").clicked() {
+        model
+            .project
+            .add_widget(WidgetEntry::Gradient(GradientWidget::new(
+                "Gradient",
+                Some("A list of colour stops, sent as stops or sampled colours"),
+                "gradient",
+                None,
+                &mut model.tether_agent,
+            )));
+    }
+
+    standard_spacer(ui);
+    ui.heading("Clipboard");
+    let selected = model.widgets_in_edit_mode();
+    if ui
+        .add_enabled(
+            !selected.is_empty(),
+            egui::Button::new(format!("📋 Copy {} in edit mode", selected.len())),
+        )
+        .on_hover_text("Ctrl+C")
+        .clicked()
+    {
+        model.queue.push(QueueItem::Copy(selected));
+    }
+    if ui.button("Paste").on_hover_text("Ctrl+V").clicked() {
+        match arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+            Ok(text) => model.paste_widgets(&text),
+            Err(e) => error!("Failed to read clipboard: {}", e),
+        }
+    }
+
+    standard_spacer(ui);
+    ui.heading("Monitors");
+    for (label, description, display) in [
+        (
+            "Text Readout",
+            "Latest message, as JSON",
+            MonitorDisplay::Text,
+        ),
+        (
+            "Gauge",
+            "Latest number, within a range",
+            MonitorDisplay::Gauge,
+        ),
+        ("LED", "Latest true or false value", MonitorDisplay::Led),
+        (
+            "Colour Swatch",
+            "Latest colour, as RGB(A)",
+            MonitorDisplay::ColourSwatch,
+        ),
+        (
+            "Point2D Scatter",
+            "Recent X and Y values",
+            MonitorDisplay::Scatter,
+        ),
+        (
+            "History Plot",
+            "Recent numbers, over time",
+            MonitorDisplay::HistoryPlot,
+        ),
+    ] {
+        if ui.button(label).clicked() {
+            model
+                .project
+                .add_widget(WidgetEntry::Monitor(Box::new(MonitorWidget::new(
+                    label,
+                    Some(description),
+                    "monitor",
+                    None,
+                    display,
+                    &mut model.tether_agent,
+                ))));
+        }
+    }
 }
 
 pub fn common_editable_values<T: Serialize>(
@@ -323,6 +553,17 @@ pub fn common_editable_values<T: Serialize>(
         }
     });
 
+    if ui
+        .checkbox(
+            &mut entry.common_mut().sync_from_network,
+            "Sync from network",
+        )
+        .on_hover_text("Update value when other Agents publish on this topic")
+        .changed()
+    {
+        entry.common_mut().update_sync_plug(tether_agent);
+    }
+
     common_edit_midi_mapping(ui, entry);
 
     ui.collapsing("Publish options", |ui| {
@@ -351,26 +592,89 @@ pub fn common_editable_values<T: Serialize>(
             ui.checkbox(&mut entry.common_mut().retain, "Retain?");
         });
     });
+
+    common_payload_encoding(ui, &mut entry.common_mut().encoding);
+
+    ui.collapsing("Repeat", |ui| {
+        let common = entry.common_mut();
+        let mut can_repeat = common.repeat.is_some();
+        if ui
+            .checkbox(&mut can_repeat, "Repeat sending")
+            .on_hover_text("Publish the current value at a fixed interval, e.g. as a heartbeat")
+            .changed()
+        {
+            common.repeat = if can_repeat {
+                Some(RepeatOptions {
+                    interval_ms: 1000,
+                    is_running: false,
+                })
+            } else {
+                None
+            };
+        }
+        if let Some(repeat) = &mut common.repeat {
+            ui.horizontal(|ui| {
+                ui.label("Interval (ms)");
+                ui.add(
+                    egui::DragValue::new(&mut repeat.interval_ms)
+                        .clamp_range(MIN_REPEAT_INTERVAL_MS..=3_600_000),
+                );
+            });
+        }
+    });
+}
+
+pub fn common_payload_encoding(ui: &mut egui::Ui, encoding: &mut PayloadEncoding) {
+    ui.collapsing("Payload encoding", |ui| {
+        ui.group(|ui| {
+            ui.label("Format");
+            ui.radio_value(
+                &mut encoding.format,
+                PayloadFormat::MessagePack,
+                "MessagePack",
+            );
+            ui.radio_value(&mut encoding.format, PayloadFormat::JsonText, "JSON text")
+                .on_hover_text("UTF-8 JSON, for receivers without MessagePack");
+        });
+        ui.group(|ui| {
+            ui.label("Shape");
+            ui.radio_value(&mut encoding.shape, PayloadShape::AsIs, "As is");
+            ui.radio_value(&mut encoding.shape, PayloadShape::Array, "Array")
+                .on_hover_text("Maps become arrays of their values");
+            ui.radio_value(&mut encoding.shape, PayloadShape::Map, "Map")
+                .on_hover_text("Arrays become maps, using the field names");
+            ui.add_enabled_ui(encoding.shape != PayloadShape::AsIs, |ui| {
+                ui.label("Field names (comma-separated)");
+                let mut text = encoding.field_names.join(", ");
+                if ui.text_edit_singleline(&mut text).changed() {
+                    encoding.field_names = if text.trim().is_empty() {
+                        Vec::new()
+                    } else {
+                        text.split(',').map(|s| String::from(s.trim())).collect()
+                    };
+                }
+            });
+        });
+        ui.group(|ui| {
+            ui.label("Numbers");
+            egui::ComboBox::from_id_source(ui.id().with("number_type"))
+                .selected_text(format!("{:?}", encoding.number_type))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut encoding.number_type, NumberType::AsIs, "AsIs");
+                    ui.selectable_value(&mut encoding.number_type, NumberType::F32, "F32");
+                    ui.selectable_value(&mut encoding.number_type, NumberType::F64, "F64");
+                    ui.selectable_value(&mut encoding.number_type, NumberType::I32, "I32");
+                    ui.selectable_value(&mut encoding.number_type, NumberType::U8, "U8");
+                });
+        });
+    });
 }
 
 fn update_plug_definition<T: Serialize>(
     entry: &mut impl CustomWidget<T>,
     tether_agent: &mut TetherAgent,
 ) {
-    debug!("Will update plug definition");
-    debug!("QOS level: {}", entry.common().qos as i32);
-    debug!("Retain: {}", entry.common().retain);
-
-    entry.common_mut().plug = PlugOptionsBuilder::create_output(&entry.common().plug_name)
-        .qos(Some(entry.common().qos as i32))
-        .retain(Some(entry.common().retain))
-        .topic(if entry.common().use_custom_topic {
-            Some(&entry.common().custom_topic)
-        } else {
-            None
-        })
-        .build(tether_agent)
-        .expect("failed to create output")
+    entry.common_mut().update_plug_definition(tether_agent);
 }
 
 pub fn common_edit_midi_mapping<T: Serialize>(ui: &mut egui::Ui, entry: &mut impl CustomWidget<T>) {
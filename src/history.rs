@@ -0,0 +1,184 @@
+use std::time::{Duration, Instant};
+
+use log::{debug, error};
+use serde_json::{json, Value};
+
+use crate::{project::Project, widgets::WidgetEntry};
+
+const MAX_HISTORY_LENGTH: usize = 100;
+
+/// Changes are only recorded once the user has stopped interacting for this
+/// long, so that e.g. dragging a slider or typing a name is a single step
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Undo/redo history for the Widgets (and Presets, Pages) in a project, kept as a
+/// series of serialised snapshots.
+///
+/// Only changes made while the user is interacting are recorded; values
+/// written by the network, MIDI or simulations on their own never add a step.
+/// Snapshots are only taken when an interaction begins and once it has
+/// settled, rather than every frame.
+pub struct History {
+    undo_stack: Vec<Value>,
+    redo_stack: Vec<Value>,
+    /// The state from before the current interaction, if any
+    pending: Option<Value>,
+    last_input: Instant,
+}
+
+fn snapshot(project: &Project) -> Value {
+    json!({
+        "widgets": project.widgets,
+        "presets": project.presets,
+        "pages": project.pages,
+    })
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending: None,
+            last_input: Instant::now(),
+        }
+    }
+}
+
+impl History {
+    /// Forget all history, e.g. when a different project is loaded
+    pub fn reset(&mut self) {
+        *self = History::default();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty() || self.pending.is_some()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Call whenever there is user input, before it is applied; the state
+    /// is kept (once per interaction) so the change can be undone
+    pub fn begin(&mut self, project: &Project) {
+        if self.pending.is_none() {
+            self.pending = Some(snapshot(project));
+        }
+        self.last_input = Instant::now();
+    }
+
+    /// Call once per frame; records a step once the interaction has settled
+    /// and the user is no longer e.g. still dragging
+    pub fn track(&mut self, project: &Project, is_interacting: bool) {
+        if is_interacting {
+            self.last_input = Instant::now();
+        } else if self.pending.is_some() && self.last_input.elapsed() >= SETTLE_TIME {
+            self.commit(project);
+        }
+    }
+
+    /// Time until a pending interaction should be recorded, if any
+    pub fn until_settled(&self) -> Option<Duration> {
+        self.pending
+            .as_ref()
+            .map(|_| SETTLE_TIME.saturating_sub(self.last_input.elapsed()))
+    }
+
+    fn commit(&mut self, project: &Project) {
+        let previous = match self.pending.take() {
+            Some(previous) => previous,
+            None => return,
+        };
+        if without_layout(&previous) == without_layout(&snapshot(project)) {
+            return;
+        }
+        debug!("Record undo step");
+        self.undo_stack.push(previous);
+        if self.undo_stack.len() > MAX_HISTORY_LENGTH {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Returns the Widgets which were replaced, if the project was changed
+    pub fn undo(&mut self, project: &mut Project) -> Option<Vec<WidgetEntry>> {
+        // Any pending change counts as the most recent step
+        self.commit(project);
+        let previous = self.undo_stack.pop()?;
+        let current = snapshot(project);
+        match restore(project, &previous) {
+            Some(replaced) => {
+                self.redo_stack.push(current);
+                Some(replaced)
+            }
+            None => {
+                self.undo_stack.push(previous);
+                None
+            }
+        }
+    }
+
+    /// Returns the Widgets which were replaced, if the project was changed
+    pub fn redo(&mut self, project: &mut Project) -> Option<Vec<WidgetEntry>> {
+        self.commit(project);
+        let next = self.redo_stack.pop()?;
+        let current = snapshot(project);
+        match restore(project, &next) {
+            Some(replaced) => {
+                self.undo_stack.push(current);
+                Some(replaced)
+            }
+            None => {
+                self.redo_stack.push(next);
+                None
+            }
+        }
+    }
+}
+
+/// Window layouts are kept with each step (so that e.g. a removed Widget
+/// comes back where it was), but moving or resizing a window, or starting or
+/// stopping repeat sending, is not recorded as a change. Neither is typing
+/// or sending text in a String Widget, which is a message rather than a
+/// setting.
+fn without_layout(state: &Value) -> Value {
+    let mut state = state.clone();
+    if let Some(widgets) = state["widgets"].as_array_mut() {
+        for widget in widgets.iter_mut().filter_map(Value::as_object_mut) {
+            if let Some(string) = widget.get_mut("string").and_then(Value::as_object_mut) {
+                string.remove("value");
+                string.remove("history");
+            }
+            for entry in widget.values_mut() {
+                if let Some(common) = entry.get_mut("common").and_then(Value::as_object_mut) {
+                    common.remove("window");
+                    if let Some(repeat) = common.get_mut("repeat").and_then(Value::as_object_mut) {
+                        repeat.remove("isRunning");
+                    }
+                }
+            }
+        }
+    }
+    state
+}
+
+fn restore(project: &mut Project, state: &Value) -> Option<Vec<WidgetEntry>> {
+    let widgets = serde_json::from_value(state["widgets"].clone());
+    let presets = serde_json::from_value(state["presets"].clone());
+    let pages = serde_json::from_value(state["pages"].clone());
+    match (widgets, presets, pages) {
+        (Ok(widgets), Ok(presets), Ok(pages)) => {
+            project.presets = presets;
+            project.pages = pages;
+            project.active_page = project
+                .active_page
+                .min(project.pages.len().saturating_sub(1));
+            Some(std::mem::replace(&mut project.widgets, widgets))
+        }
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            error!("Failed to restore from history: {}", e);
+            None
+        }
+    }
+}
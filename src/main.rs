@@ -11,10 +11,14 @@ use clap::Parser;
 use eframe::egui;
 use env_logger::Env;
 
+mod crossfade;
 mod gui;
+mod history;
 mod midi_mapping;
 mod model;
+mod payload;
 mod project;
+mod repeater;
 mod settings;
 mod widgets;
 
@@ -5,7 +5,10 @@ use tether_agent::{PlugOptionsBuilder, TetherAgent};
 
 use crate::{
     gui::widget_view::common_send,
-    widgets::{boolean::BoolWidget, numbers::NumberWidget, CustomWidget},
+    widgets::{
+        boolean::BoolWidget, choice::ChoiceWidget, number_array::NumberArrayWidget,
+        numbers::NumberWidget, CustomWidget,
+    },
 };
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -32,6 +35,7 @@ pub struct TetherNotePayload {
 pub enum MidiMessage {
     ControlChange(TetherControlChangePayload),
     Note(TetherNotePayload),
+    NoteOff(TetherNotePayload),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -50,6 +54,7 @@ impl MidiSubscriber {
             let _midi_controllers_plug =
                 PlugOptionsBuilder::create_input("controlChange").build(agent);
             let _midi_notes_plug = PlugOptionsBuilder::create_input("notesOn").build(agent);
+            let _midi_notes_off_plug = PlugOptionsBuilder::create_input("notesOff").build(agent);
         }
         MidiSubscriber {}
     }
@@ -69,7 +74,18 @@ impl MidiSubscriber {
                 debug!("This is a Tether MIDI note on message: {}", plug_name);
                 let decoded: TetherNotePayload =
                     rmp_serde::from_slice(payload).expect("failed to decode payload");
-                Some(MidiMessage::Note(decoded))
+                // Many devices send note on with zero velocity instead of note off
+                if decoded.velocity == 0 {
+                    Some(MidiMessage::NoteOff(decoded))
+                } else {
+                    Some(MidiMessage::Note(decoded))
+                }
+            }
+            "notesOff" => {
+                debug!("This is a Tether MIDI note off message: {}", plug_name);
+                let decoded: TetherNotePayload =
+                    rmp_serde::from_slice(payload).expect("failed to decode payload");
+                Some(MidiMessage::NoteOff(decoded))
             }
             _ => None,
         }
@@ -97,12 +113,10 @@ pub fn update_widget_if_controllable(
             MidiMapping::Set(mapping) => {
                 if mapping.channel == *channel && mapping.controller_or_note == *controller {
                     debug!("Message matches MIDI mapping, should update");
-                    let output_range = entry.range();
                     let should_round = entry.should_round();
-                    // let input_range = T::from_f64(0.)..=T::from_f64(127.0);
-                    let input_range = 0. ..=127.;
-                    let midi_value_number = *value as f64;
-                    let remapped_value = remap(midi_value_number, input_range, output_range);
+                    // Along the Widget's response curve, rather than linear
+                    let position = remap(*value as f64, 0. ..=127., 0. ..=1.);
+                    let remapped_value = entry.value_at_position(position);
                     let v = entry.value_mut();
                     *v = if should_round {
                         remapped_value.round()
@@ -116,19 +130,73 @@ pub fn update_widget_if_controllable(
     }
 }
 
+/// Like `update_widget_if_controllable`, but for each element of the array
+/// with its own mapping; the whole array is sent if any element changed
+pub fn update_array_if_controllable(
+    entry: &mut NumberArrayWidget,
+    cc_message: &TetherControlChangePayload,
+    tether_agent: &TetherAgent,
+) {
+    let TetherControlChangePayload {
+        channel,
+        controller,
+        value,
+    } = cc_message;
+    let mut matched = Vec::new();
+    for (i, midi_mapping) in entry.element_midi_mut().iter_mut().enumerate() {
+        match midi_mapping {
+            Some(MidiMapping::Learning) => {
+                *midi_mapping = Some(MidiMapping::Set(MidiMapped {
+                    channel: *channel,
+                    controller_or_note: *controller,
+                }));
+            }
+            Some(MidiMapping::Set(mapping))
+                if mapping.channel == *channel && mapping.controller_or_note == *controller =>
+            {
+                matched.push(i);
+            }
+            _ => {}
+        }
+    }
+    if matched.is_empty() {
+        return;
+    }
+    debug!(
+        "Message matches MIDI mapping of array elements {:?}",
+        matched
+    );
+    // Along the Widget's response curve, as for Number Widgets
+    let position = remap(*value as f64, 0. ..=127., 0. ..=1.);
+    for i in matched {
+        let remapped_value = entry.element_value_at_position(i, position);
+        entry.set_element(i, remapped_value);
+    }
+    entry.send(tether_agent);
+}
+
 pub fn send_if_midi_note<T: Serialize>(
     entry: &mut impl CustomWidget<T>,
     note_message: &TetherNotePayload,
 ) -> bool {
-    if let Some(midi_mapping) = &entry.common().midi_mapping {
+    note_matches_mapping(&mut entry.common_mut().midi_mapping, note_message)
+}
+
+/// Check whether a note message matches the given mapping, or if the mapping
+/// is still learning, assign it to this note (and return false)
+pub fn note_matches_mapping(
+    midi_mapping: &mut Option<MidiMapping>,
+    note_message: &TetherNotePayload,
+) -> bool {
+    if let Some(mapping) = midi_mapping {
         let TetherNotePayload {
             channel,
             note,
             velocity: _,
         } = note_message;
-        match midi_mapping {
+        match mapping {
             MidiMapping::Learning => {
-                entry.common_mut().midi_mapping = Some(MidiMapping::Set(MidiMapped {
+                *midi_mapping = Some(MidiMapping::Set(MidiMapped {
                     channel: *channel,
                     controller_or_note: *note,
                 }));
@@ -163,10 +231,42 @@ pub fn toggle_if_midi_note(
             }
             MidiMapping::Set(mapping) => {
                 if mapping.channel == *channel && mapping.controller_or_note == *note {
-                    *entry.value_mut() = !*entry.value();
+                    // Momentary buttons are held until the note is released
+                    *entry.value_mut() = entry.is_momentary() || !*entry.value();
                     common_send(entry, tether_agent);
                 }
             }
         }
     }
 }
+
+/// Release a momentary button (and send), if the note matches
+pub fn release_if_midi_note(
+    entry: &mut BoolWidget,
+    note_message: &TetherNotePayload,
+    tether_agent: &TetherAgent,
+) {
+    if !entry.is_momentary() {
+        return;
+    }
+    if let Some(MidiMapping::Set(mapping)) = &entry.common().midi_mapping {
+        if mapping.channel == note_message.channel
+            && mapping.controller_or_note == note_message.note
+        {
+            *entry.value_mut() = false;
+            common_send(entry, tether_agent);
+        }
+    }
+}
+
+/// Step through the options (and send), if the note matches
+pub fn next_option_if_midi_note(
+    entry: &mut ChoiceWidget,
+    note_message: &TetherNotePayload,
+    tether_agent: &TetherAgent,
+) {
+    if send_if_midi_note(entry, note_message) {
+        entry.next_option();
+        common_send(entry, tether_agent);
+    }
+}
@@ -1,25 +1,36 @@
-use std::time::Duration;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use tether_agent::{three_part_topic::TetherOrCustomTopic, TetherAgent, TetherAgentOptionsBuilder};
 use tether_utils::tether_topics::{insights::Insights, TopicOptions};
 
 use crate::{
+    crossfade::Crossfade,
     gui::{
         render,
         tether_gui_utils::{unconnected_tether_agent, EditableTetherSettings},
         utilities_view::{PlaybackState, RecordingState},
-        widget_view::common_send,
+        widget_view::{common_send, entry_payload, send_entry},
     },
+    history::History,
     midi_mapping::{
-        send_if_midi_note, toggle_if_midi_note, update_widget_if_controllable, MidiMessage,
-        MidiSubscriber,
+        next_option_if_midi_note, note_matches_mapping, release_if_midi_note, send_if_midi_note,
+        toggle_if_midi_note, update_array_if_controllable, update_widget_if_controllable,
+        MidiMessage, MidiSubscriber,
     },
     project::{try_load, Project},
+    repeater::Repeater,
     settings::Cli,
-    widgets::WidgetEntry,
+    widgets::{CustomWidget, WidgetEntry},
 };
 use clap::Parser;
+use egui::{Key, Modifiers};
+
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const REPEAT_COUNT_REFRESH: Duration = Duration::from_millis(250);
 
 pub struct Model {
     pub tether_agent: TetherAgent,
@@ -27,6 +38,8 @@ pub struct Model {
     pub json_file: Option<String>,
     pub monitor_topic: String,
     pub project: Project,
+    /// Last error encountered while loading (or migrating) a project file, if any
+    pub project_error: Option<String>,
     pub queue: Vec<QueueItem>,
     pub insights: Option<Insights>,
     pub message_log_filter: String,
@@ -35,6 +48,28 @@ pub struct Model {
     pub active_window: ActiveView,
     pub playback: PlaybackState,
     pub recording: RecordingState,
+    /// Set via CLI; broadcast on every project load, even if the project
+    /// itself does not ask for it
+    pub force_broadcast_on_load: bool,
+    /// IDs of Widgets still waiting to be broadcast, in order; not indexes,
+    /// since Widgets may be removed or reordered during the broadcast
+    pub broadcast_queue: VecDeque<u64>,
+    pub last_broadcast: Option<Instant>,
+    /// Name to use for the next Preset saved
+    pub new_preset_name: String,
+    pub crossfade: Option<Crossfade>,
+    pub history: History,
+    /// Publishes the Widgets which are set to repeat, in the background
+    pub repeater: Repeater,
+    /// Part of every Widget window's ID; incremented to make egui forget
+    /// its own window state, so that the layout stored in the project
+    /// (see `Common::window`) is applied again
+    pub layout_generation: u64,
+    /// Perform mode: all editing is hidden until unlocked with the project's PIN
+    pub is_locked: bool,
+    /// PIN as typed so far, while locked
+    pub unlock_attempt: String,
+    pub unlock_failed: bool,
 }
 
 impl Default for Model {
@@ -44,7 +79,14 @@ impl Default for Model {
         let json_path: String = cli.json_load.unwrap_or(String::from("./project.json"));
         info!("Will attempt to load JSON from {} ...", &json_path);
 
-        let (project, was_loaded_from_disk) = try_load(&json_path);
+        let (project, was_loaded_from_disk, project_error) = match try_load(&json_path) {
+            Ok(Some(project)) => (project, true, None),
+            Ok(None) => (Project::default(), false, None),
+            Err(e) => {
+                error!("Failed to load project: {}", e);
+                (Project::default(), false, Some(e.to_string()))
+            }
+        };
 
         let tether_settings = match &project.tether_settings {
             Some(s) => s.clone(),
@@ -54,6 +96,8 @@ impl Default for Model {
         let tether_agent =
             unconnected_tether_agent(&TetherAgentOptionsBuilder::from(tether_settings));
 
+        let history = History::default();
+
         let mut init_model = Model {
             tether_agent,
             // edit_tether_settings: false,
@@ -66,6 +110,7 @@ impl Default for Model {
             },
             monitor_topic: cli.monitor_topic.clone(),
             project,
+            project_error,
             queue: Vec::new(),
             insights: None,
             message_log_filter: "".into(),
@@ -74,8 +119,26 @@ impl Default for Model {
             active_window: ActiveView::WidgetView,
             playback: PlaybackState::default(),
             recording: RecordingState::default(),
+            force_broadcast_on_load: cli.broadcast_on_load,
+            broadcast_queue: VecDeque::new(),
+            last_broadcast: None,
+            new_preset_name: String::from("Preset"),
+            crossfade: None,
+            history,
+            repeater: Repeater::new(),
+            layout_generation: 0,
+            is_locked: cli.locked,
+            unlock_attempt: String::new(),
+            unlock_failed: false,
         };
 
+        init_model
+            .project
+            .restore_plug_definitions(&mut init_model.tether_agent);
+        if was_loaded_from_disk {
+            init_model.queue_broadcast_on_load();
+        }
+
         if cli.tether_disable {
             warn!("Tether disabled; please connect manually if required");
         } else {
@@ -98,6 +161,11 @@ impl eframe::App for Model {
                 if let Some(insights) = &mut self.insights {
                     insights.update(&topic, payload.to_vec());
                 }
+                for widget in self.project.widgets.iter_mut() {
+                    if widget.sync_from_message(&topic, &payload) {
+                        debug!("Synced widget \"{}\" from network", widget.common().name);
+                    }
+                }
                 let plug_name: String = match topic {
                     TetherOrCustomTopic::Custom(topic) => {
                         error!("Invalid Tether Topic \"{}\"", &topic);
@@ -124,6 +192,13 @@ impl eframe::App for Model {
                                             &self.tether_agent,
                                         );
                                     }
+                                    WidgetEntry::NumberArray(e) => {
+                                        update_array_if_controllable(
+                                            e,
+                                            &cc_message,
+                                            &self.tether_agent,
+                                        );
+                                    }
                                     _ => {}
                                 }
                             }
@@ -134,6 +209,13 @@ impl eframe::App for Model {
                                     WidgetEntry::Bool(e) => {
                                         toggle_if_midi_note(e, &note_message, &self.tether_agent);
                                     }
+                                    WidgetEntry::Choice(e) => {
+                                        next_option_if_midi_note(
+                                            e,
+                                            &note_message,
+                                            &self.tether_agent,
+                                        );
+                                    }
                                     WidgetEntry::Empty(e) => {
                                         if send_if_midi_note(e, &note_message) {
                                             common_send(e, &self.tether_agent);
@@ -144,12 +226,37 @@ impl eframe::App for Model {
                                             e.publish_from_json_string(&self.tether_agent);
                                         }
                                     }
+                                    WidgetEntry::String(e) => {
+                                        if send_if_midi_note(e, &note_message) {
+                                            e.send(&self.tether_agent);
+                                        }
+                                    }
                                     WidgetEntry::Colour(e) => {
                                         if send_if_midi_note(e, &note_message) {
-                                            common_send(e, &self.tether_agent);
+                                            e.send(&self.tether_agent);
+                                        }
+                                    }
+                                    WidgetEntry::NumberArray(e) => {
+                                        if send_if_midi_note(e, &note_message) {
+                                            e.send(&self.tether_agent);
+                                        }
+                                    }
+                                    WidgetEntry::Gradient(e) => {
+                                        if send_if_midi_note(e, &note_message) {
+                                            e.send(&self.tether_agent);
                                         }
                                     }
                                     WidgetEntry::Point2D(e) => {
+                                        if send_if_midi_note(e, &note_message) {
+                                            e.send(&self.tether_agent);
+                                        }
+                                    }
+                                    WidgetEntry::Point3D(e) => {
+                                        if send_if_midi_note(e, &note_message) {
+                                            common_send(e, &self.tether_agent);
+                                        }
+                                    }
+                                    WidgetEntry::Tracking(e) => {
                                         if send_if_midi_note(e, &note_message) {
                                             common_send(e, &self.tether_agent);
                                         }
@@ -157,18 +264,71 @@ impl eframe::App for Model {
                                     _ => {}
                                 }
                             }
+                            let recalled = self.project.presets.iter_mut().position(|preset| {
+                                note_matches_mapping(&mut preset.midi_mapping, &note_message)
+                            });
+                            if let Some(index) = recalled {
+                                self.recall_preset(index);
+                            }
+                        }
+                        Some(MidiMessage::NoteOff(note_message)) => {
+                            for widget in self.project.widgets.iter_mut() {
+                                if let WidgetEntry::Bool(e) = widget {
+                                    release_if_midi_note(e, &note_message, &self.tether_agent);
+                                }
+                            }
                         }
                         None => {}
                     }
                 }
             }
         }
+        if self.tether_agent.is_connected() {
+            self.process_broadcast_queue(ctx);
+        }
+        self.process_crossfade(ctx);
+        self.update_simulations(ctx);
+        self.update_repeats(ctx);
+
         if !work_done {
             std::thread::sleep(Duration::from_millis(1));
         }
 
         if self.continuous_mode {
             ctx.request_repaint();
+        } else if self
+            .project
+            .widgets
+            .iter()
+            .any(|w| w.common().sync_from_network || matches!(w, WidgetEntry::Monitor(_)))
+        {
+            // Keep checking for incoming values even without user interaction
+            ctx.request_repaint_after(SYNC_POLL_INTERVAL);
+        }
+
+        if has_user_input(ctx) {
+            // Anything changed from here until the input settles is one undo
+            // step; changes from the network, MIDI or simulations alone are not
+            self.history.begin(&self.project);
+        }
+
+        if !self.is_locked && ctx.memory(|m| m.focus()).is_none() {
+            let (copy_requested, pasted) = ctx.input(|i| {
+                (
+                    i.events.contains(&egui::Event::Copy),
+                    i.events.iter().find_map(|e| match e {
+                        egui::Event::Paste(text) => Some(text.clone()),
+                        _ => None,
+                    }),
+                )
+            });
+            if copy_requested {
+                self.queue
+                    .push(QueueItem::Copy(self.widgets_in_edit_mode()));
+            }
+            if let Some(text) = pasted {
+                self.paste_widgets(&text);
+            }
         }
 
         while let Some(q) = self.queue.pop() {
@@ -176,14 +336,271 @@ impl eframe::App for Model {
                 QueueItem::Remove(index) => {
                     self.project.widgets.remove(index);
                 }
+                QueueItem::Duplicate(index) => {
+                    self.project.duplicate_widget(index, &mut self.tether_agent);
+                }
+                QueueItem::Copy(indexes) => {
+                    // Leave whatever is already on the clipboard alone
+                    if indexes.is_empty() {
+                        continue;
+                    }
+                    let text = self.project.widgets_to_json(&indexes);
+                    info!("Copied {} widget(s) to clipboard", indexes.len());
+                    ctx.output_mut(|o| o.copied_text = text);
+                }
+                QueueItem::SetNumber(id, value) => {
+                    for widget in self.project.widgets.iter_mut() {
+                        match widget {
+                            WidgetEntry::FloatNumber(e) | WidgetEntry::WholeNumber(e)
+                                if e.common().id == id =>
+                            {
+                                e.apply_change(value, &self.tether_agent);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        if !self.is_locked && ctx.memory(|m| m.focus()).is_none() {
+            // Text fields have their own undo/redo, so only handle these otherwise
+            if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)) {
+                self.redo();
+            } else if ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::Z)) {
+                self.undo();
             }
         }
 
         render(ctx, self);
+
+        let is_interacting = ctx.input(|i| i.pointer.any_down());
+        self.history.track(&self.project, is_interacting);
+        if let Some(wait) = self.history.until_settled() {
+            ctx.request_repaint_after(wait);
+        }
     }
 }
 
 impl Model {
+    /// Queue up all Widgets for broadcast, if either the project or the CLI asks for it;
+    /// messages are only actually sent once the Agent is connected
+    pub fn queue_broadcast_on_load(&mut self) {
+        if self.project.broadcast_on_load.enabled || self.force_broadcast_on_load {
+            self.queue_broadcast();
+        }
+    }
+
+    pub fn queue_broadcast(&mut self) {
+        info!(
+            "Will broadcast values for {} widgets",
+            self.project.widgets.len()
+        );
+        self.broadcast_queue = self.project.broadcast_order().into();
+        self.last_broadcast = None;
+    }
+
+    pub fn widgets_in_edit_mode(&self) -> Vec<usize> {
+        self.project
+            .widgets
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| w.common().is_edit_mode())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn paste_widgets(&mut self, text: &str) {
+        match self.project.paste_widgets(text, &mut self.tether_agent) {
+            Ok(count) => info!("Pasted {} widget(s)", count),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    pub fn undo(&mut self) {
+        self.restore_from_history(History::undo);
+    }
+
+    pub fn redo(&mut self) {
+        self.restore_from_history(History::redo);
+    }
+
+    fn restore_from_history(
+        &mut self,
+        step: fn(&mut History, &mut Project) -> Option<Vec<WidgetEntry>>,
+    ) {
+        let mut replaced = match step(&mut self.history, &mut self.project) {
+            Some(replaced) => replaced,
+            None => return,
+        };
+        self.project
+            .restore_plug_definitions(&mut self.tether_agent);
+        // Edit mode, window layout, repeat sending, String Widget text and
+        // subscriptions are not part of the history, so keep these for the
+        // Widgets that (still) exist, and only subscribe again where the
+        // settings have changed
+        for widget in self.project.widgets.iter_mut() {
+            let id = widget.common().id;
+            match replaced.iter_mut().find(|w| w.common().id == id) {
+                Some(previous) => {
+                    let is_edit_mode = previous.common().is_edit_mode();
+                    widget.common_mut().set_edit_mode(is_edit_mode);
+                    widget.common_mut().window = previous.common().window;
+                    widget.common_mut().keep_repeat(previous.common_mut());
+                    if let (WidgetEntry::String(e), WidgetEntry::String(p)) =
+                        (&mut *widget, &mut *previous)
+                    {
+                        e.keep_text(p);
+                    }
+                    if !widget.keep_subscription(previous) {
+                        widget.subscribe(&mut self.tether_agent);
+                    }
+                }
+                None => widget.subscribe(&mut self.tether_agent),
+            }
+        }
+        self.restore_layout();
+    }
+
+    /// Widgets which publish on their own (e.g. simulated tracking), whether
+    /// or not they are currently shown
+    fn update_simulations(&mut self, ctx: &egui::Context) {
+        for widget in self.project.widgets.iter_mut() {
+            if let WidgetEntry::Tracking(e) = widget {
+                if let Some(wait) = e.update(&self.tether_agent) {
+                    ctx.request_repaint_after(wait);
+                }
+            }
+        }
+    }
+
+    /// Start or stop background sending for the Widgets which are set to
+    /// repeat, passing on any change to their values; the sending itself is
+    /// done by the Repeater, whether or not the UI repaints
+    fn update_repeats(&mut self, ctx: &egui::Context) {
+        for widget in self.project.widgets.iter_mut() {
+            let payload = if widget.common().is_repeating() {
+                entry_payload(widget)
+            } else {
+                None
+            };
+            widget.common_mut().update_repeat(payload, &self.repeater);
+        }
+        if self
+            .project
+            .widgets
+            .iter()
+            .any(|w| w.common().is_repeating())
+        {
+            // Only to keep the send counts shown up to date
+            ctx.request_repaint_after(REPEAT_COUNT_REFRESH);
+        }
+    }
+
+    /// Enter perform mode; Widgets currently being edited are closed first
+    pub fn lock(&mut self) {
+        for widget in self.project.widgets.iter_mut() {
+            widget.common_mut().set_edit_mode(false);
+        }
+        self.unlock_attempt.clear();
+        self.unlock_failed = false;
+        self.is_locked = true;
+        info!("Entered perform mode");
+    }
+
+    /// Leave perform mode if the PIN typed so far matches the project's PIN
+    pub fn try_unlock(&mut self) {
+        if self.unlock_attempt == self.project.perform_pin {
+            self.is_locked = false;
+            self.unlock_failed = false;
+            info!("Left perform mode");
+        } else {
+            warn!("Wrong PIN entered to leave perform mode");
+            self.unlock_failed = true;
+        }
+        self.unlock_attempt.clear();
+    }
+
+    /// Re-apply the window layout stored in the project
+    pub fn restore_layout(&mut self) {
+        self.layout_generation += 1;
+    }
+
+    /// Write the stored values of a Preset back into the Widgets, publishing
+    /// each one if the Preset asks for it. If a crossfade duration is set, the
+    /// values are instead interpolated over time; see `process_crossfade`
+    pub fn recall_preset(&mut self, index: usize) {
+        if let Some(preset) = self.project.presets.get(index) {
+            if self.project.crossfade.duration_ms > 0 {
+                info!("Crossfade to preset \"{}\"", &preset.name);
+                self.crossfade = Some(Crossfade::new(
+                    &preset.name,
+                    self.project.current_values(),
+                    preset.values.clone(),
+                    preset.publish_on_recall,
+                    &self.project.crossfade,
+                ));
+                return;
+            }
+            self.crossfade = None;
+            info!("Recall preset \"{}\"", &preset.name);
+            for widget in self.project.widgets.iter_mut() {
+                if let Some(value) = preset.values.get(&widget.common().id) {
+                    if widget.set_value_json(value) && preset.publish_on_recall {
+                        send_entry(widget, &self.tether_agent);
+                    }
+                }
+            }
+        }
+    }
+
+    fn process_crossfade(&mut self, ctx: &egui::Context) {
+        if let Some(crossfade) = &mut self.crossfade {
+            if let Some(values) = crossfade.step(&self.project.widgets) {
+                for widget in self.project.widgets.iter_mut() {
+                    let id = widget.common().id;
+                    if let Some((_, value)) = values.iter().find(|(v_id, _)| *v_id == id) {
+                        if widget.set_value_json(value) && crossfade.publish {
+                            send_entry(widget, &self.tether_agent);
+                        }
+                    }
+                }
+            }
+            if crossfade.is_finished() {
+                info!("Crossfade to \"{}\" complete", &crossfade.preset_name);
+                self.crossfade = None;
+            } else {
+                ctx.request_repaint_after(crossfade.until_next_step());
+            }
+        }
+    }
+
+    /// Send as many queued Widget values as the configured delay allows right now
+    fn process_broadcast_queue(&mut self, ctx: &egui::Context) {
+        let delay = Duration::from_millis(self.project.broadcast_on_load.delay_ms);
+        while !self.broadcast_queue.is_empty() {
+            if let Some(last) = self.last_broadcast {
+                let elapsed = last.elapsed();
+                if elapsed < delay {
+                    ctx.request_repaint_after(delay.saturating_sub(elapsed));
+                    return;
+                }
+            }
+            if let Some(id) = self.broadcast_queue.pop_front() {
+                // Widgets may have been removed since the broadcast was queued
+                if let Some(entry) = self
+                    .project
+                    .widgets
+                    .iter_mut()
+                    .find(|w| w.common().id == id)
+                {
+                    send_entry(entry, &self.tether_agent);
+                }
+            }
+            self.last_broadcast = Some(Instant::now());
+        }
+    }
+
     /// Always creates a new Tether Agent instance, using the settings either loaded from the
     /// current "project"
     /// or defaults if none are available.
@@ -193,8 +610,11 @@ impl Model {
             None => EditableTetherSettings::default(),
         };
 
-        self.tether_agent =
-            unconnected_tether_agent(&TetherAgentOptionsBuilder::from(tether_settings));
+        let options = TetherAgentOptionsBuilder::from(tether_settings);
+        self.tether_agent = unconnected_tether_agent(&options);
+        self.repeater.connect(&options);
+        self.project
+            .restore_plug_definitions(&mut self.tether_agent);
 
         match self.tether_agent.connect() {
             Ok(()) => {
@@ -209,6 +629,7 @@ impl Model {
                     &mut self.tether_agent,
                 ));
                 self.midi_handler = Some(MidiSubscriber::new(&mut self.tether_agent));
+                self.project.subscribe_sync_plugs(&mut self.tether_agent);
             }
             Err(e) => {
                 error!("Failed to connect Tether Agent: {}", e);
@@ -217,6 +638,23 @@ impl Model {
     }
 }
 
+/// Whether the user did anything this frame which might change the project
+fn has_user_input(ctx: &egui::Context) -> bool {
+    ctx.input(|i| {
+        i.pointer.any_down()
+            || i.events.iter().any(|e| {
+                matches!(
+                    e,
+                    egui::Event::PointerButton { .. }
+                        | egui::Event::Key { .. }
+                        | egui::Event::Text(_)
+                        | egui::Event::Paste(_)
+                        | egui::Event::Cut
+                )
+            })
+    })
+}
+
 #[derive(PartialEq)]
 pub enum ActiveView {
     WidgetView,
@@ -225,4 +663,10 @@ pub enum ActiveView {
 
 pub enum QueueItem {
     Remove(usize),
+    Duplicate(usize),
+    /// Copy the Widgets at these indexes to the clipboard, as JSON
+    Copy(Vec<usize>),
+    /// Set the value of the Number Widget with this ID, e.g. from the XY pad
+    /// of the Widget it is paired with
+    SetNumber(u64, f64),
 }
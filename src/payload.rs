@@ -0,0 +1,178 @@
+use anyhow::anyhow;
+use serde::{ser::SerializeMap, ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PayloadFormat {
+    MessagePack,
+    /// UTF-8 JSON text, for receivers without a MessagePack decoder
+    JsonText,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PayloadShape {
+    /// Whatever the Widget's value type produces
+    AsIs,
+    /// Maps become arrays of their values, in field-name order if given
+    Array,
+    /// Arrays become maps, using the field names in order
+    Map,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// Applied to every number in the payload; whole-number types are rounded
+/// and clamped
+pub enum NumberType {
+    AsIs,
+    F32,
+    F64,
+    I32,
+    U8,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// How a Widget's value is turned into a message payload. The default
+/// (MessagePack, as is) matches the Widget's own value type exactly.
+pub struct PayloadEncoding {
+    pub format: PayloadFormat,
+    pub shape: PayloadShape,
+    /// Used for the Map (and Array) shapes; missing names become "0", "1"...
+    pub field_names: Vec<String>,
+    pub number_type: NumberType,
+}
+
+impl Default for PayloadEncoding {
+    fn default() -> Self {
+        PayloadEncoding {
+            format: PayloadFormat::MessagePack,
+            shape: PayloadShape::AsIs,
+            field_names: Vec::new(),
+            number_type: NumberType::AsIs,
+        }
+    }
+}
+
+impl PayloadEncoding {
+    fn field_name(&self, index: usize) -> String {
+        match self.field_names.get(index) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => index.to_string(),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        let value = self.reshape(serde_json::to_value(value)?);
+        let encoded = EncodedValue {
+            value: &value,
+            number_type: self.number_type,
+        };
+        match self.format {
+            PayloadFormat::MessagePack => Ok(rmp_serde::to_vec_named(&encoded)?),
+            PayloadFormat::JsonText => Ok(serde_json::to_vec(&encoded)?),
+        }
+    }
+
+    /// Decode an incoming payload in this encoding; maps are turned back into
+    /// arrays (by field name) if the Map shape is used
+    pub fn decode(&self, payload: &[u8]) -> anyhow::Result<Value> {
+        let value: Value = match self.format {
+            PayloadFormat::MessagePack => rmp_serde::from_slice(payload)?,
+            PayloadFormat::JsonText => serde_json::from_slice(payload)?,
+        };
+        match (self.shape, value) {
+            (PayloadShape::Map, Value::Object(mut map)) => {
+                let count = if self.field_names.is_empty() {
+                    map.len()
+                } else {
+                    self.field_names.len()
+                };
+                (0..count)
+                    .map(|i| {
+                        let name = self.field_name(i);
+                        map.remove(&name)
+                            .ok_or_else(|| anyhow!("Payload has no field \"{}\"", name))
+                    })
+                    .collect::<anyhow::Result<Vec<Value>>>()
+                    .map(Value::Array)
+            }
+            (_, value) => Ok(value),
+        }
+    }
+
+    fn reshape(&self, value: Value) -> Value {
+        match (self.shape, value) {
+            (PayloadShape::Map, Value::Array(elements)) => Value::Object(
+                elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| (self.field_name(i), v))
+                    .collect::<Map<String, Value>>(),
+            ),
+            (PayloadShape::Array, Value::Object(mut map)) if !self.field_names.is_empty() => {
+                Value::Array(
+                    self.field_names
+                        .iter()
+                        .map(|name| map.remove(name).unwrap_or(Value::Null))
+                        .collect(),
+                )
+            }
+            (PayloadShape::Array, Value::Object(map)) => {
+                Value::Array(map.into_iter().map(|(_, v)| v).collect())
+            }
+            (_, value) => value,
+        }
+    }
+}
+
+/// Serialises a JSON value with every number written as the given type, so
+/// that e.g. MessagePack gets real 32-bit floats
+struct EncodedValue<'a> {
+    value: &'a Value,
+    number_type: NumberType,
+}
+
+impl<'a> EncodedValue<'a> {
+    fn child(&self, value: &'a Value) -> Self {
+        EncodedValue {
+            value,
+            number_type: self.number_type,
+        }
+    }
+}
+
+impl<'a> Serialize for EncodedValue<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Number(n) => {
+                let f = n.as_f64().unwrap_or_default();
+                match self.number_type {
+                    NumberType::AsIs => n.serialize(serializer),
+                    NumberType::F32 => serializer.serialize_f32(f as f32),
+                    NumberType::F64 => serializer.serialize_f64(f),
+                    NumberType::I32 => serializer
+                        .serialize_i32(f.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32),
+                    NumberType::U8 => serializer.serialize_u8(f.round().clamp(0., 255.) as u8),
+                }
+            }
+            Value::Array(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for v in elements {
+                    seq.serialize_element(&self.child(v))?;
+                }
+                seq.end()
+            }
+            Value::Object(map) => {
+                let mut m = serializer.serialize_map(Some(map.len()))?;
+                for (k, v) in map {
+                    m.serialize_entry(k, &self.child(v))?;
+                }
+                m.end()
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info};
+use tether_agent::{TetherAgent, TetherAgentOptionsBuilder};
+
+use crate::gui::tether_gui_utils::unconnected_tether_agent;
+
+/// How long the thread waits for new work when nothing is repeating
+const IDLE_WAIT: Duration = Duration::from_secs(1);
+/// After a failed connection, wait this long before trying again, doubling
+/// each time up to the maximum
+const MIN_RETRY_WAIT: Duration = Duration::from_secs(1);
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, PartialEq, Debug)]
+/// Everything needed to publish one Widget's value repeatedly
+pub struct RepeatJob {
+    pub topic: String,
+    pub qos: i32,
+    pub retain: bool,
+    pub interval: Duration,
+    /// The current value, already encoded
+    pub payload: Vec<u8>,
+}
+
+#[derive(Default, Debug)]
+/// Shared between a Widget and the background thread while it repeats
+pub struct RepeatShared {
+    sent_count: AtomicU64,
+    /// Sends which were due but could not be made, e.g. while not connected
+    failed_count: AtomicU64,
+    is_stopped: AtomicBool,
+}
+
+impl RepeatShared {
+    pub fn sent_count(&self) -> u64 {
+        self.sent_count.load(Ordering::Relaxed)
+    }
+
+    pub fn failed_count(&self) -> u64 {
+        self.failed_count.load(Ordering::Relaxed)
+    }
+
+    /// The thread drops the job the next time it checks
+    pub fn stop(&self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.is_stopped.load(Ordering::Relaxed)
+    }
+}
+
+enum Command {
+    Connect(TetherAgentOptionsBuilder),
+    /// Start a job, or update one already running with the same shared state
+    Run(RepeatJob, Arc<RepeatShared>),
+}
+
+/// Publishes the Widgets which are set to repeat on a background thread,
+/// through its own Agent, so that the timing does not depend on the UI
+pub struct Repeater {
+    commands: Sender<Command>,
+}
+
+impl Repeater {
+    pub fn new() -> Self {
+        let (commands, receiver) = mpsc::channel();
+        thread::spawn(move || run(receiver));
+        Repeater { commands }
+    }
+
+    /// Use these settings for the thread's own Agent; it only connects once
+    /// there is something to send
+    pub fn connect(&self, options: &TetherAgentOptionsBuilder) {
+        self.send(Command::Connect(options.clone()));
+    }
+
+    pub fn run(&self, job: RepeatJob, shared: Arc<RepeatShared>) {
+        self.send(Command::Run(job, shared));
+    }
+
+    fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            error!("Repeat sending thread is no longer running");
+        }
+    }
+}
+
+struct Scheduled {
+    job: RepeatJob,
+    shared: Arc<RepeatShared>,
+    next_send: Instant,
+}
+
+fn run(receiver: Receiver<Command>) {
+    let mut options: Option<TetherAgentOptionsBuilder> = None;
+    let mut agent: Option<TetherAgent> = None;
+    let mut retry_wait = MIN_RETRY_WAIT;
+    let mut retry_at: Option<Instant> = None;
+    let mut scheduled: Vec<Scheduled> = Vec::new();
+
+    loop {
+        scheduled.retain(|s| !s.shared.is_stopped());
+        let now = Instant::now();
+        let wait = scheduled
+            .iter()
+            .map(|s| s.next_send.saturating_duration_since(now))
+            .min()
+            .unwrap_or(IDLE_WAIT);
+        match receiver.recv_timeout(wait) {
+            Ok(Command::Connect(new_options)) => {
+                options = Some(new_options);
+                agent = None;
+                retry_wait = MIN_RETRY_WAIT;
+                retry_at = None;
+            }
+            Ok(Command::Run(job, shared)) => {
+                match scheduled
+                    .iter_mut()
+                    .find(|s| Arc::ptr_eq(&s.shared, &shared))
+                {
+                    Some(s) => {
+                        if s.job.interval != job.interval {
+                            s.next_send = Instant::now();
+                        }
+                        s.job = job;
+                    }
+                    None => scheduled.push(Scheduled {
+                        job,
+                        shared,
+                        next_send: Instant::now(),
+                    }),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // The app has closed
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if scheduled.is_empty() {
+            continue;
+        }
+        let now = Instant::now();
+        let is_retry_due = match retry_at {
+            Some(retry_at) => now >= retry_at,
+            None => true,
+        };
+        if let (None, Some(options), true) = (&agent, &options, is_retry_due) {
+            let mut new_agent = unconnected_tether_agent(options);
+            match new_agent.connect() {
+                Ok(()) => {
+                    info!("Connected Agent for repeat sending");
+                    agent = Some(new_agent);
+                    retry_wait = MIN_RETRY_WAIT;
+                    retry_at = None;
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to connect Agent for repeat sending, will retry in {:?}: {}",
+                        retry_wait, e
+                    );
+                    retry_at = Some(now + retry_wait);
+                    retry_wait = (retry_wait * 2).min(MAX_RETRY_WAIT);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for s in scheduled.iter_mut().filter(|s| now >= s.next_send) {
+            if s.shared.is_stopped() {
+                continue;
+            }
+            let job = &s.job;
+            let result = match &agent {
+                Some(agent) => {
+                    agent.publish_raw(&job.topic, &job.payload, Some(job.qos), Some(job.retain))
+                }
+                None => Err(anyhow::anyhow!("not connected")),
+            };
+            match result {
+                Ok(()) => {
+                    s.shared.sent_count.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    debug!("Repeat send failed: {}", e);
+                    s.shared.failed_count.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            // Skip any sends missed entirely, rather than sending them all
+            // at once
+            let next = s.next_send + job.interval;
+            s.next_send = if next < now { now + job.interval } else { next };
+        }
+    }
+}
@@ -18,6 +18,16 @@ pub struct Cli {
     #[arg(long = "continuous")]
     pub continuous_mode: bool,
 
+    /// Flag to publish the current value of every Widget once connected, whenever
+    /// a project is loaded (regardless of the project's own setting)
+    #[arg(long = "broadcast-on-load")]
+    pub broadcast_on_load: bool,
+
+    /// Flag to start in perform (locked) mode, with all editing hidden; unlocking
+    /// needs the PIN saved in the project, if any
+    #[arg(long = "locked")]
+    pub locked: bool,
+
     #[arg(long = "loglevel",default_value_t=String::from("info"))]
     pub log_level: String,
 }
@@ -1,4 +1,4 @@
-use egui::Ui;
+use egui::{Button, Ui};
 use serde::{Deserialize, Serialize};
 use tether_agent::TetherAgent;
 
@@ -17,6 +17,13 @@ use super::{Common, CustomWidget, View};
 pub struct BoolWidget {
     common: Common,
     value: bool,
+    /// True while held (by mouse or MIDI note), false when released, instead
+    /// of toggling
+    #[serde(default)]
+    momentary: bool,
+    /// Whether the mouse is currently holding the momentary button
+    #[serde(skip)]
+    pressed: bool,
 }
 
 impl BoolWidget {
@@ -31,6 +38,30 @@ impl BoolWidget {
         BoolWidget {
             common: Common::new(widget_name, description, plug_name, custom_topic, agent),
             value: init_state,
+            momentary: false,
+            pressed: false,
+        }
+    }
+
+    pub fn is_momentary(&self) -> bool {
+        self.momentary
+    }
+
+    fn render_momentary(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        let label = if self.value { "HELD" } else { "Hold" };
+        let mut button = Button::new(label);
+        if self.value {
+            button = button.fill(ui.visuals().selection.bg_fill);
+        }
+        let response = ui.add_sized([120., 40.], button);
+        let down = response.is_pointer_button_down_on();
+        if down != self.pressed {
+            self.pressed = down;
+            self.value = down;
+            common_send(self, tether_agent);
+        }
+        if common_send_button(ui, self, false).clicked() {
+            common_send(self, tether_agent);
         }
     }
 }
@@ -60,13 +91,20 @@ impl View for BoolWidget {
                 MidiMapping::Learning => {}
                 MidiMapping::Set(mapping) => {
                     ui.label(format!(
-                        "MIDI mapped: toggle on ch {} note {}",
-                        mapping.channel, mapping.controller_or_note
+                        "MIDI mapped: {} on ch {} note {}",
+                        if self.momentary { "hold" } else { "toggle" },
+                        mapping.channel,
+                        mapping.controller_or_note
                     ));
                 }
             }
         }
 
+        if self.momentary {
+            self.render_momentary(ui, tether_agent);
+            return;
+        }
+
         let checked = *self.value();
         if ui
             .checkbox(
@@ -89,6 +127,8 @@ impl View for BoolWidget {
 
     fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
         common_editable_values(ui, self, tether_agent);
+        ui.checkbox(&mut self.momentary, "Momentary")
+            .on_hover_text("Send true when pressed and false when released, instead of toggling");
         common_save_button(ui, self, tether_agent);
     }
 }
@@ -0,0 +1,229 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tether_agent::TetherAgent;
+
+use crate::{
+    gui::widget_view::{
+        common_editable_values, common_in_use_heading, common_save_button, common_send,
+        common_send_button,
+    },
+    midi_mapping::MidiMapping,
+};
+
+use super::{Common, CustomWidget, View};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ChoiceStyle {
+    ComboBox,
+    Radio,
+    Buttons,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// A label shown to the user, and the value published when it is chosen
+pub struct ChoiceOption {
+    pub label: String,
+    pub value: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Publishes the value of one of a list of user-defined options, e.g. a mode
+pub struct ChoiceWidget {
+    common: Common,
+    /// The value of the chosen option; the option itself is found by value,
+    /// so that it stays correct when set from a Preset or the network
+    value: Value,
+    options: Vec<ChoiceOption>,
+    style: ChoiceStyle,
+}
+
+impl ChoiceWidget {
+    pub fn new(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        options: &[&str],
+        agent: &mut TetherAgent,
+    ) -> Self {
+        let options: Vec<ChoiceOption> = options
+            .iter()
+            .map(|s| ChoiceOption {
+                label: String::from(*s),
+                value: Value::from(*s),
+            })
+            .collect();
+        ChoiceWidget {
+            common: Common::new(widget_name, description, plug_name, custom_topic, agent),
+            value: options.first().map(|o| o.value.clone()).unwrap_or_default(),
+            options,
+            style: ChoiceStyle::ComboBox,
+        }
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        self.options.iter().position(|o| o.value == self.value)
+    }
+
+    /// Choose the next option, wrapping around after the last one
+    pub fn next_option(&mut self) {
+        if self.options.is_empty() {
+            return;
+        }
+        let next = match self.selected_index() {
+            Some(i) => (i + 1) % self.options.len(),
+            None => 0,
+        };
+        self.value = self.options[next].value.clone();
+    }
+}
+
+/// Strings are shown without quotes, unless they would otherwise be
+/// read back as some other JSON type (e.g. the string "42")
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) if serde_json::from_str::<Value>(s).is_err() => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Anything that is not valid JSON is taken as a string
+fn value_from_text(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::from(text))
+}
+
+impl CustomWidget<Value> for ChoiceWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &Value {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut Value {
+        &mut self.value
+    }
+}
+
+impl View for ChoiceWidget {
+    fn render_in_use(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+
+        if let Some(midi) = &self.common().midi_mapping {
+            match midi {
+                MidiMapping::Learning => {}
+                MidiMapping::Set(mapping) => {
+                    ui.label(format!(
+                        "MIDI mapped: next option on ch {} note {}",
+                        mapping.channel, mapping.controller_or_note
+                    ));
+                }
+            }
+        }
+
+        let selected = self.selected_index();
+        let mut chosen = None;
+        match self.style {
+            ChoiceStyle::ComboBox => {
+                egui::ComboBox::from_id_source(ui.id().with("choice"))
+                    .selected_text(match selected {
+                        Some(i) => self.options[i].label.clone(),
+                        None => value_to_text(&self.value),
+                    })
+                    .show_ui(ui, |ui| {
+                        for (i, option) in self.options.iter().enumerate() {
+                            if ui
+                                .selectable_label(selected == Some(i), &option.label)
+                                .clicked()
+                            {
+                                chosen = Some(i);
+                            }
+                        }
+                    });
+            }
+            ChoiceStyle::Radio => {
+                for (i, option) in self.options.iter().enumerate() {
+                    if ui.radio(selected == Some(i), &option.label).clicked() {
+                        chosen = Some(i);
+                    }
+                }
+            }
+            ChoiceStyle::Buttons => {
+                ui.horizontal_wrapped(|ui| {
+                    for (i, option) in self.options.iter().enumerate() {
+                        if ui
+                            .selectable_label(selected == Some(i), &option.label)
+                            .clicked()
+                        {
+                            chosen = Some(i);
+                        }
+                    }
+                });
+            }
+        }
+        if selected.is_none() {
+            ui.small(format!("Value: {}", value_to_text(&self.value)));
+        }
+
+        let mut should_send = false;
+        if let Some(i) = chosen {
+            self.value = self.options[i].value.clone();
+            should_send = self.common().auto_send;
+        }
+        if common_send_button(ui, self, true).clicked() || should_send {
+            common_send(self, tether_agent);
+        }
+    }
+
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        common_editable_values(ui, self, tether_agent);
+
+        ui.label("Style");
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.style, ChoiceStyle::ComboBox, "Dropdown");
+            ui.radio_value(&mut self.style, ChoiceStyle::Radio, "Radio");
+            ui.radio_value(&mut self.style, ChoiceStyle::Buttons, "Buttons");
+        });
+
+        ui.label("Options");
+        ui.small("Values are strings, unless valid JSON (e.g. 42, true, {\"a\": 1})");
+        let mut to_remove = None;
+        egui::Grid::new(ui.id().with("options"))
+            .num_columns(3)
+            .show(ui, |ui| {
+                ui.label("Label");
+                ui.label("Value");
+                ui.end_row();
+                for (i, option) in self.options.iter_mut().enumerate() {
+                    ui.text_edit_singleline(&mut option.label);
+                    let mut text = value_to_text(&option.value);
+                    if ui.text_edit_singleline(&mut text).changed() {
+                        option.value = value_from_text(&text);
+                    }
+                    if ui.button("❌").clicked() {
+                        to_remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = to_remove {
+            self.options.remove(i);
+        }
+        if ui.button("＋ Add option").clicked() {
+            let label = format!("option {}", self.options.len() + 1);
+            self.options.push(ChoiceOption {
+                value: Value::from(label.as_str()),
+                label,
+            });
+        }
+
+        common_save_button(ui, self, tether_agent);
+    }
+}
@@ -1,11 +1,15 @@
-use egui::Ui;
+use egui::{
+    ecolor::{hsv_from_rgb, rgb_from_hsv},
+    Color32, Sense, Slider, Ui,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use tether_agent::TetherAgent;
 
 use crate::{
     gui::widget_view::{
-        common_editable_values, common_in_use_heading, common_save_button, common_send,
-        common_send_button,
+        common_editable_values, common_in_use_heading, common_save_button, common_send_button,
+        publish_value,
     },
     midi_mapping::MidiMapping,
 };
@@ -14,11 +18,61 @@ use super::{Common, CustomWidget, View};
 
 pub type ColourRGBA8 = [u8; 4];
 
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// How the colour is published; the alpha channel is optional in each case
+pub enum ColourOutput {
+    /// `[r, g, b, a]`, each 0-255
+    Rgba8,
+    /// `[r, g, b, a]`, each 0.0-1.0
+    FloatRgb,
+    /// `[h, s, v, a]`, each 0.0-1.0
+    Hsv,
+    /// `"#rrggbbaa"`
+    Hex,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ColourWidget<ColourRGBA8> {
     common: Common,
     value: ColourRGBA8,
+    #[serde(default = "default_output")]
+    output: ColourOutput,
+    #[serde(default = "default_alpha")]
+    alpha: bool,
+    /// Saved swatches, each selected with a single click
+    #[serde(default)]
+    palette: Vec<ColourRGBA8>,
+    /// Hue and saturation are lost for greys and black, so the slider values
+    /// are kept for as long as the colour they were set to
+    #[serde(skip)]
+    hsv: Option<(ColourRGBA8, [f32; 3])>,
+}
+
+/// Project files from before output formats were added sent `[r, g, b, a]`
+fn default_output() -> ColourOutput {
+    ColourOutput::Rgba8
+}
+
+fn default_alpha() -> bool {
+    true
+}
+
+fn parse_hex(text: &str) -> Option<ColourRGBA8> {
+    let digits = text.trim().trim_start_matches('#');
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+        return None;
+    }
+    let mut colour = [255; 4];
+    for (i, channel) in colour.iter_mut().enumerate().take(digits.len() / 2) {
+        *channel = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(colour)
+}
+
+fn to_u8(channel: f64) -> u8 {
+    channel.round().clamp(0., 255.) as u8
 }
 
 impl ColourWidget<ColourRGBA8> {
@@ -32,6 +86,142 @@ impl ColourWidget<ColourRGBA8> {
         ColourWidget {
             common: Common::new(widget_name, description, plug_name, custom_topic, agent),
             value: [255, 255, 255, 255],
+            output: ColourOutput::Rgba8,
+            alpha: true,
+            palette: Vec::new(),
+            hsv: None,
+        }
+    }
+
+    /// Hue, saturation and value (0-1) of the sRGB colour, as most agents
+    /// expect, rather than egui's linear-space `Hsva`
+    fn hsv(&self) -> [f32; 3] {
+        match self.hsv {
+            Some((rgba, hsv)) if rgba == self.value => hsv,
+            _ => {
+                let [r, g, b, _] = self.value.map(|c| c as f32 / 255.);
+                let (h, s, v) = hsv_from_rgb([r, g, b]);
+                [h, s, v]
+            }
+        }
+    }
+
+    fn set_hsv(&mut self, hsv: [f32; 3]) {
+        let [r, g, b] = rgb_from_hsv((hsv[0], hsv[1], hsv[2]));
+        let [r, g, b] = [r, g, b].map(|c| to_u8(c as f64 * 255.));
+        self.value = [r, g, b, self.value[3]];
+        self.hsv = Some((self.value, hsv));
+    }
+
+    /// The value as published, in the chosen output format
+    pub fn output(&self) -> Value {
+        let channels: Vec<f64> = match self.output {
+            ColourOutput::Rgba8 => self.value.map(|c| c as f64).to_vec(),
+            ColourOutput::FloatRgb => self.value.map(|c| c as f64 / 255.).to_vec(),
+            ColourOutput::Hsv => {
+                let [h, s, v] = self.hsv();
+                vec![h as f64, s as f64, v as f64, self.value[3] as f64 / 255.]
+            }
+            ColourOutput::Hex => {
+                let [r, g, b, a] = self.value;
+                return Value::from(if self.alpha {
+                    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+                } else {
+                    format!("#{:02x}{:02x}{:02x}", r, g, b)
+                });
+            }
+        };
+        let count = if self.alpha { 4 } else { 3 };
+        match self.output {
+            // Whole numbers, as before output formats were added
+            ColourOutput::Rgba8 => json!(channels[..count]
+                .iter()
+                .map(|c| *c as u8)
+                .collect::<Vec<u8>>()),
+            _ => json!(channels[..count]),
+        }
+    }
+
+    /// Set the value from a message in the chosen output format (e.g. when
+    /// synced from the network). Returns false if it could not be read.
+    pub fn set_from_output(&mut self, value: &Value) -> bool {
+        if let (ColourOutput::Hex, Value::String(text)) = (self.output, value) {
+            return match parse_hex(text) {
+                Some(colour) => {
+                    self.value = colour;
+                    true
+                }
+                None => false,
+            };
+        }
+        let channels: Vec<f64> = match value.as_array() {
+            Some(elements) if elements.len() == 3 || elements.len() == 4 => {
+                match elements.iter().map(Value::as_f64).collect() {
+                    Some(channels) => channels,
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        let scale = match self.output {
+            ColourOutput::Rgba8 => 1.,
+            _ => 255.,
+        };
+        let alpha = channels.get(3).map_or(255, |a| to_u8(a * scale));
+        match self.output {
+            ColourOutput::Hsv => {
+                self.value[3] = alpha;
+                self.set_hsv([channels[0] as f32, channels[1] as f32, channels[2] as f32]);
+            }
+            _ => {
+                let [r, g, b] = [0, 1, 2].map(|i| to_u8(channels[i] * scale));
+                self.value = [r, g, b, alpha];
+            }
+        }
+        true
+    }
+
+    pub fn send(&self, tether_agent: &TetherAgent) {
+        publish_value(self.common(), &self.output(), tether_agent);
+    }
+
+    /// Returns true if a saved colour was chosen
+    fn render_palette(&mut self, ui: &mut Ui) -> bool {
+        let mut chosen = None;
+        let mut to_remove = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, [r, g, b, a]) in self.palette.iter().enumerate() {
+                let (rect, response) = ui.allocate_exact_size(egui::vec2(20., 20.), Sense::click());
+                ui.painter()
+                    .rect_filled(rect, 2., Color32::from_rgba_unmultiplied(*r, *g, *b, *a));
+                if response.hovered() {
+                    ui.painter()
+                        .rect_stroke(rect, 2., ui.visuals().widgets.hovered.fg_stroke);
+                }
+                let response = response.on_hover_text("Click to use, right-click to remove");
+                if response.clicked() {
+                    chosen = Some(i);
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Remove").clicked() {
+                        to_remove = Some(i);
+                        ui.close_menu();
+                    }
+                });
+            }
+            if ui.small_button("＋").on_hover_text("Save colour").clicked() {
+                self.palette.push(self.value);
+            }
+        });
+        if let Some(i) = to_remove {
+            self.palette.remove(i);
+        }
+        match chosen {
+            Some(i) => {
+                self.value = self.palette[i];
+                true
+            }
+            None => false,
         }
     }
 }
@@ -68,18 +258,47 @@ impl View for ColourWidget<ColourRGBA8> {
             }
         }
 
-        if ui
+        let mut changed = ui
             .color_edit_button_srgba_unmultiplied(self.value_mut())
-            .changed()
-            && self.common().auto_send
-            || common_send_button(ui, self, true).clicked()
-        {
-            common_send(self, tether_agent);
+            .changed();
+
+        let mut hsv = self.hsv();
+        let mut hsv_changed = false;
+        for (channel, name) in hsv.iter_mut().zip(["H", "S", "V"]) {
+            hsv_changed |= ui.add(Slider::new(channel, 0. ..=1.).text(name)).changed();
+        }
+        if hsv_changed {
+            self.set_hsv(hsv);
+            changed = true;
+        }
+
+        if self.render_palette(ui) {
+            changed = true;
+        }
+
+        ui.small(format!("Output: {}", self.output()));
+
+        if changed && self.common().auto_send || common_send_button(ui, self, true).clicked() {
+            self.send(tether_agent);
         }
     }
 
     fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
         common_editable_values(ui, self, tether_agent);
+
+        ui.label("Output format");
+        ui.horizontal_wrapped(|ui| {
+            ui.radio_value(&mut self.output, ColourOutput::Rgba8, "RGB 0-255");
+            ui.radio_value(&mut self.output, ColourOutput::FloatRgb, "RGB 0-1");
+            ui.radio_value(&mut self.output, ColourOutput::Hsv, "HSV 0-1");
+            ui.radio_value(&mut self.output, ColourOutput::Hex, "Hex string");
+        });
+        ui.checkbox(&mut self.alpha, "Include alpha");
+
+        if ui.button("Clear palette").clicked() {
+            self.palette.clear();
+        }
+
         common_save_button(ui, self, tether_agent);
     }
 }
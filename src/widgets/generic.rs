@@ -1,5 +1,5 @@
 use egui::{Color32, Ui};
-use log::{debug, error};
+use log::error;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tether_agent::TetherAgent;
@@ -7,6 +7,7 @@ use tether_agent::TetherAgent;
 use crate::{
     gui::widget_view::{
         common_editable_values, common_in_use_heading, common_save_button, common_send_button,
+        publish_value,
     },
     midi_mapping::MidiMapping,
 };
@@ -45,13 +46,7 @@ impl GenericJSONWidget {
 
     pub fn publish_from_json_string(&self, tether_agent: &TetherAgent) {
         match serde_json::from_str::<serde_json::Value>(&self.value) {
-            Ok(encoded) => {
-                let payload = rmp_serde::to_vec_named(&encoded).expect("failed to encode msgpack");
-                match tether_agent.publish(&self.common().plug, Some(&payload)) {
-                    Ok(()) => debug!("Send OK"),
-                    Err(_) => error!("Failed to send; connected? {}", tether_agent.is_connected()),
-                }
-            }
+            Ok(value) => publish_value(self.common(), &value, tether_agent),
             Err(e) => {
                 error!("Could not serialise String -> JSON; error: {}", e);
             }
@@ -0,0 +1,294 @@
+use egui::{Color32, DragValue, Rect, Sense, Slider, Stroke, Ui};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tether_agent::TetherAgent;
+
+use crate::{
+    gui::widget_view::{
+        common_editable_values, common_in_use_heading, common_save_button, common_send_button,
+        publish_value,
+    },
+    midi_mapping::MidiMapping,
+};
+
+use super::{colours::ColourRGBA8, Common, CustomWidget, View};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ColourStop {
+    /// 0-1 along the gradient
+    pub position: f32,
+    pub colour: ColourRGBA8,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum GradientOutput {
+    /// `[{position, colour: [r, g, b, a]}, ...]`, sorted by position
+    Stops,
+    /// `[[r, g, b, a], ...]`, evenly spaced from start to end
+    Samples,
+}
+
+const PREVIEW_HEIGHT: f32 = 24.0;
+const MAX_SAMPLES: usize = 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Publishes a whole gradient or palette, e.g. for lighting
+pub struct GradientWidget {
+    common: Common,
+    value: Vec<ColourStop>,
+    output: GradientOutput,
+    /// Number of colours published in the Samples output
+    sample_count: usize,
+}
+
+impl GradientWidget {
+    pub fn new(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        agent: &mut TetherAgent,
+    ) -> Self {
+        GradientWidget {
+            common: Common::new(widget_name, description, plug_name, custom_topic, agent),
+            value: vec![
+                ColourStop {
+                    position: 0.,
+                    colour: [0, 0, 0, 255],
+                },
+                ColourStop {
+                    position: 1.,
+                    colour: [255, 255, 255, 255],
+                },
+            ],
+            output: GradientOutput::Samples,
+            sample_count: 8,
+        }
+    }
+
+    fn sorted_stops(&self) -> Vec<ColourStop> {
+        let mut stops = self.value.clone();
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        stops
+    }
+
+    /// Colour at the given position (0-1), interpolated between the
+    /// surrounding stops; black if there are none
+    pub fn sample(&self, position: f32) -> ColourRGBA8 {
+        sample_stops(&self.sorted_stops(), position)
+    }
+
+    pub fn samples(&self) -> Vec<ColourRGBA8> {
+        let stops = self.sorted_stops();
+        let count = self.sample_count.max(1);
+        (0..count)
+            .map(|i| {
+                let position = if count == 1 {
+                    0.
+                } else {
+                    i as f32 / (count - 1) as f32
+                };
+                sample_stops(&stops, position)
+            })
+            .collect()
+    }
+
+    /// The value as published, in the chosen output format
+    pub fn output(&self) -> Value {
+        match self.output {
+            GradientOutput::Stops => serde_json::to_value(self.sorted_stops()),
+            GradientOutput::Samples => serde_json::to_value(self.samples()),
+        }
+        .unwrap_or_default()
+    }
+
+    /// Set the value from a message in the chosen output format (e.g. when
+    /// synced from the network); samples become evenly spaced stops.
+    /// Returns false if it could not be read.
+    pub fn set_from_output(&mut self, value: &Value) -> bool {
+        let stops = match self.output {
+            GradientOutput::Stops => serde_json::from_value::<Vec<ColourStop>>(value.clone()).ok(),
+            GradientOutput::Samples => serde_json::from_value::<Vec<ColourRGBA8>>(value.clone())
+                .ok()
+                .map(|colours| {
+                    let last = colours.len().saturating_sub(1).max(1) as f32;
+                    colours
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, colour)| ColourStop {
+                            position: i as f32 / last,
+                            colour,
+                        })
+                        .collect()
+                }),
+        };
+        match stops {
+            Some(stops) => {
+                self.value = stops;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn send(&self, tether_agent: &TetherAgent) {
+        publish_value(self.common(), &self.output(), tether_agent);
+    }
+
+    fn render_preview(&self, ui: &mut Ui) {
+        let width = ui.available_width().max(100.);
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, PREVIEW_HEIGHT), Sense::hover());
+        let stops = self.sorted_stops();
+        let steps = (width / 2.).ceil() as usize;
+        let step_width = width / steps as f32;
+        for i in 0..steps {
+            let [r, g, b, a] = sample_stops(&stops, (i as f32 + 0.5) / steps as f32);
+            let x = rect.left() + i as f32 * step_width;
+            ui.painter().rect_filled(
+                Rect::from_x_y_ranges(x..=x + step_width + 0.5, rect.y_range()),
+                0.,
+                Color32::from_rgba_unmultiplied(r, g, b, a),
+            );
+        }
+        let stroke = Stroke::new(1.0, ui.visuals().text_color());
+        for stop in &stops {
+            let x = rect.left() + stop.position.clamp(0., 1.) * width;
+            ui.painter()
+                .vline(x, rect.bottom() - 6.0..=rect.bottom(), stroke);
+        }
+        if self.output == GradientOutput::Samples {
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing.x = 1.0;
+                for [r, g, b, a] in self.samples() {
+                    let (rect, _) = ui.allocate_exact_size(egui::vec2(10., 10.), Sense::hover());
+                    ui.painter()
+                        .rect_filled(rect, 0., Color32::from_rgba_unmultiplied(r, g, b, a));
+                }
+            });
+        }
+    }
+}
+
+fn lerp_colour(a: ColourRGBA8, b: ColourRGBA8, t: f32) -> ColourRGBA8 {
+    let mut colour = a;
+    for (i, channel) in colour.iter_mut().enumerate() {
+        *channel = (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8;
+    }
+    colour
+}
+
+/// Stops must be sorted by position
+fn sample_stops(stops: &[ColourStop], position: f32) -> ColourRGBA8 {
+    match stops.iter().position(|s| s.position >= position) {
+        None => stops.last().map_or([0, 0, 0, 255], |s| s.colour),
+        Some(0) => stops[0].colour,
+        Some(i) => {
+            let (before, after) = (stops[i - 1], stops[i]);
+            let span = after.position - before.position;
+            if span <= 0. {
+                after.colour
+            } else {
+                lerp_colour(
+                    before.colour,
+                    after.colour,
+                    (position - before.position) / span,
+                )
+            }
+        }
+    }
+}
+
+impl CustomWidget<Vec<ColourStop>> for GradientWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &Vec<ColourStop> {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut Vec<ColourStop> {
+        &mut self.value
+    }
+}
+
+impl View for GradientWidget {
+    fn render_in_use(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+
+        if let Some(midi) = &self.common().midi_mapping {
+            match midi {
+                MidiMapping::Learning => {}
+                MidiMapping::Set(mapping) => {
+                    ui.label(format!(
+                        "MIDI mapped: send on ch {} note {}",
+                        mapping.channel, mapping.controller_or_note
+                    ));
+                }
+            }
+        }
+
+        self.render_preview(ui);
+
+        let mut changed = false;
+        let mut to_remove = None;
+        let can_remove = self.value.len() > 1;
+        for (i, stop) in self.value.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .color_edit_button_srgba_unmultiplied(&mut stop.colour)
+                    .changed();
+                changed |= ui.add(Slider::new(&mut stop.position, 0. ..=1.)).changed();
+                if can_remove && ui.small_button("❌").clicked() {
+                    to_remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = to_remove {
+            self.value.remove(i);
+            changed = true;
+        }
+        if ui.button("＋ Add stop").clicked() {
+            // In the widest gap, so that the gradient looks the same until edited
+            let stops = self.sorted_stops();
+            let position = stops
+                .windows(2)
+                .max_by(|a, b| {
+                    (a[1].position - a[0].position).total_cmp(&(b[1].position - b[0].position))
+                })
+                .map_or(0.5, |pair| (pair[0].position + pair[1].position) / 2.);
+            self.value.push(ColourStop {
+                position,
+                colour: self.sample(position),
+            });
+            changed = true;
+        }
+
+        if changed && self.common().auto_send || common_send_button(ui, self, true).clicked() {
+            self.send(tether_agent);
+        }
+    }
+
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        common_editable_values(ui, self, tether_agent);
+
+        ui.label("Output");
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.output, GradientOutput::Stops, "Stops");
+            ui.radio_value(&mut self.output, GradientOutput::Samples, "Samples");
+        });
+        ui.add_enabled_ui(self.output == GradientOutput::Samples, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Sample count");
+                ui.add(DragValue::new(&mut self.sample_count).clamp_range(1..=MAX_SAMPLES));
+            });
+        });
+
+        common_save_button(ui, self, tether_agent);
+    }
+}
@@ -1,24 +1,50 @@
-use serde::{Deserialize, Serialize};
-use tether_agent::{PlugDefinition, PlugOptionsBuilder, TetherAgent};
+use std::{sync::Arc, time::Duration};
 
-use crate::midi_mapping::MidiMapping;
+use log::{debug, error, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tether_agent::{
+    three_part_topic::TetherOrCustomTopic, PlugDefinition, PlugOptionsBuilder, TetherAgent,
+};
+
+use crate::{
+    midi_mapping::MidiMapping,
+    payload::PayloadEncoding,
+    repeater::{RepeatJob, RepeatShared, Repeater},
+};
 
 use self::{
     boolean::BoolWidget,
+    choice::ChoiceWidget,
     colours::{ColourRGBA8, ColourWidget},
     empty::EmptyWidget,
     generic::GenericJSONWidget,
+    gradient::GradientWidget,
+    monitors::MonitorWidget,
+    number_array::NumberArrayWidget,
     numbers::NumberWidget,
     point::Point2DWidget,
+    point3d::Point3DWidget,
+    string::StringWidget,
+    tracking::TrackingWidget,
 };
 
 // Re-export modules
 pub mod boolean;
+pub mod choice;
 pub mod colours;
 pub mod empty;
 pub mod generic;
+pub mod gradient;
+pub mod monitors;
+pub mod number_array;
 pub mod numbers;
 pub mod point;
+pub mod point3d;
+pub mod response_curve;
+pub mod rng;
+pub mod string;
+pub mod tracking;
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -27,11 +53,204 @@ pub mod point;
 pub enum WidgetEntry {
     FloatNumber(NumberWidget),
     WholeNumber(NumberWidget),
+    NumberArray(NumberArrayWidget),
     Colour(ColourWidget<ColourRGBA8>),
+    Gradient(GradientWidget),
     Bool(BoolWidget),
     Empty(EmptyWidget),
     Point2D(Point2DWidget),
+    Point3D(Point3DWidget),
+    Tracking(TrackingWidget),
     Generic(GenericJSONWidget),
+    String(StringWidget),
+    Choice(ChoiceWidget),
+    Monitor(Box<MonitorWidget>),
+}
+
+impl WidgetEntry {
+    pub fn common(&self) -> &Common {
+        match self {
+            WidgetEntry::FloatNumber(e) => e.common(),
+            WidgetEntry::WholeNumber(e) => e.common(),
+            WidgetEntry::NumberArray(e) => e.common(),
+            WidgetEntry::Colour(e) => e.common(),
+            WidgetEntry::Gradient(e) => e.common(),
+            WidgetEntry::Bool(e) => e.common(),
+            WidgetEntry::Empty(e) => e.common(),
+            WidgetEntry::Point2D(e) => e.common(),
+            WidgetEntry::Point3D(e) => e.common(),
+            WidgetEntry::Tracking(e) => e.common(),
+            WidgetEntry::Generic(e) => e.common(),
+            WidgetEntry::String(e) => e.common(),
+            WidgetEntry::Choice(e) => e.common(),
+            WidgetEntry::Monitor(e) => e.common(),
+        }
+    }
+
+    pub fn common_mut(&mut self) -> &mut Common {
+        match self {
+            WidgetEntry::FloatNumber(e) => e.common_mut(),
+            WidgetEntry::WholeNumber(e) => e.common_mut(),
+            WidgetEntry::NumberArray(e) => e.common_mut(),
+            WidgetEntry::Colour(e) => e.common_mut(),
+            WidgetEntry::Gradient(e) => e.common_mut(),
+            WidgetEntry::Bool(e) => e.common_mut(),
+            WidgetEntry::Empty(e) => e.common_mut(),
+            WidgetEntry::Point2D(e) => e.common_mut(),
+            WidgetEntry::Point3D(e) => e.common_mut(),
+            WidgetEntry::Tracking(e) => e.common_mut(),
+            WidgetEntry::Generic(e) => e.common_mut(),
+            WidgetEntry::String(e) => e.common_mut(),
+            WidgetEntry::Choice(e) => e.common_mut(),
+            WidgetEntry::Monitor(e) => e.common_mut(),
+        }
+    }
+
+    pub fn view_mut(&mut self) -> &mut dyn View {
+        match self {
+            WidgetEntry::FloatNumber(e) => e,
+            WidgetEntry::WholeNumber(e) => e,
+            WidgetEntry::NumberArray(e) => e,
+            WidgetEntry::Colour(e) => e,
+            WidgetEntry::Gradient(e) => e,
+            WidgetEntry::Bool(e) => e,
+            WidgetEntry::Empty(e) => e,
+            WidgetEntry::Point2D(e) => e,
+            WidgetEntry::Point3D(e) => e,
+            WidgetEntry::Tracking(e) => e,
+            WidgetEntry::Generic(e) => e,
+            WidgetEntry::String(e) => e,
+            WidgetEntry::Choice(e) => e,
+            WidgetEntry::Monitor(e) => e.as_mut(),
+        }
+    }
+
+    /// Subscribe for "sync from network" (if enabled) or, for Monitors, to
+    /// the monitored topic; only possible once the Agent is connected
+    pub fn subscribe(&mut self, tether_agent: &mut TetherAgent) {
+        match self {
+            WidgetEntry::Monitor(e) => e.subscribe(tether_agent),
+            _ => self.common_mut().update_sync_plug(tether_agent),
+        }
+    }
+
+    /// Reuse the subscription of an earlier version of the same Widget (e.g.
+    /// from before an undo) if it was made with the same settings. Returns
+    /// false if it needs to subscribe again.
+    pub fn keep_subscription(&mut self, previous: &mut WidgetEntry) -> bool {
+        if !self.common().has_same_subscription(previous.common()) {
+            return false;
+        }
+        match (self, previous) {
+            (WidgetEntry::Monitor(e), WidgetEntry::Monitor(previous)) => {
+                e.keep_subscription(previous);
+                true
+            }
+            (WidgetEntry::Monitor(_), _) | (_, WidgetEntry::Monitor(_)) => false,
+            (e, previous) => {
+                e.common_mut().sync_plug = previous.common_mut().sync_plug.take();
+                true
+            }
+        }
+    }
+
+    /// The current value as JSON, e.g. for storing in a Preset; None for
+    /// Widgets which have no (user-set) value
+    pub fn value_json(&self) -> Option<Value> {
+        match self {
+            WidgetEntry::FloatNumber(e) => value_to_json(e),
+            WidgetEntry::WholeNumber(e) => value_to_json(e),
+            WidgetEntry::NumberArray(e) => value_to_json(e),
+            WidgetEntry::Colour(e) => value_to_json(e),
+            WidgetEntry::Gradient(e) => value_to_json(e),
+            WidgetEntry::Bool(e) => value_to_json(e),
+            WidgetEntry::Point2D(e) => value_to_json(e),
+            WidgetEntry::Point3D(e) => value_to_json(e),
+            WidgetEntry::Tracking(e) => value_to_json(e),
+            WidgetEntry::Generic(e) => value_to_json(e),
+            WidgetEntry::String(e) => value_to_json(e),
+            WidgetEntry::Choice(e) => value_to_json(e),
+            WidgetEntry::Empty(_) | WidgetEntry::Monitor(_) => None,
+        }
+    }
+
+    /// Replace the current value from JSON (without publishing). Returns true
+    /// if the JSON matched the Widget's value type.
+    pub fn set_value_json(&mut self, value: &Value) -> bool {
+        match self {
+            WidgetEntry::FloatNumber(e) => value_from_json(e, value),
+            WidgetEntry::WholeNumber(e) => value_from_json(e, value),
+            WidgetEntry::NumberArray(e) => value_from_json(e, value),
+            WidgetEntry::Colour(e) => value_from_json(e, value),
+            WidgetEntry::Gradient(e) => value_from_json(e, value),
+            WidgetEntry::Bool(e) => value_from_json(e, value),
+            WidgetEntry::Point2D(e) => value_from_json(e, value),
+            WidgetEntry::Point3D(e) => value_from_json(e, value),
+            WidgetEntry::Tracking(e) => value_from_json(e, value),
+            WidgetEntry::Generic(e) => value_from_json(e, value),
+            WidgetEntry::String(e) => value_from_json(e, value),
+            WidgetEntry::Choice(e) => value_from_json(e, value),
+            WidgetEntry::Empty(_) | WidgetEntry::Monitor(_) => false,
+        }
+    }
+
+    /// Apply a message received on the Widget's own topic, if it has
+    /// "sync from network" enabled. Returns true if the value was updated.
+    pub fn sync_from_message(&mut self, topic: &TetherOrCustomTopic, payload: &[u8]) -> bool {
+        if let WidgetEntry::Monitor(e) = self {
+            return e.receive(topic, payload);
+        }
+        if !self.common().should_sync_from(topic) {
+            return false;
+        }
+        match self {
+            WidgetEntry::FloatNumber(e) | WidgetEntry::WholeNumber(e) => {
+                let is_synced = sync_value(e, payload);
+                if is_synced {
+                    e.constrain_value();
+                }
+                is_synced
+            }
+            WidgetEntry::NumberArray(e) => sync_value(e, payload),
+            WidgetEntry::Colour(e) => match e.common().encoding.decode(payload) {
+                Ok(value) => e.set_from_output(&value),
+                Err(e) => {
+                    warn!("Could not decode payload for sync: {}", e);
+                    false
+                }
+            },
+            WidgetEntry::Gradient(e) => match e.common().encoding.decode(payload) {
+                Ok(value) => e.set_from_output(&value),
+                Err(e) => {
+                    warn!("Could not decode payload for sync: {}", e);
+                    false
+                }
+            },
+            WidgetEntry::Bool(e) => sync_value(e, payload),
+            WidgetEntry::Point2D(e) => match e.common().encoding.decode(payload) {
+                Ok(value) => e.set_from_output(&value),
+                Err(e) => {
+                    warn!("Could not decode payload for sync: {}", e);
+                    false
+                }
+            },
+            WidgetEntry::Point3D(e) => sync_value(e, payload),
+            WidgetEntry::Tracking(e) => sync_value(e, payload),
+            WidgetEntry::String(e) => sync_value(e, payload),
+            WidgetEntry::Choice(e) => sync_value(e, payload),
+            WidgetEntry::Empty(_) | WidgetEntry::Monitor(_) => false,
+            WidgetEntry::Generic(e) => match e.common().encoding.decode(payload) {
+                Ok(value) => {
+                    *e.value_mut() = value.to_string();
+                    true
+                }
+                Err(e) => {
+                    warn!("Could not decode payload for sync: {}", e);
+                    false
+                }
+            },
+        }
+    }
 }
 
 pub trait CustomWidget<T: Serialize> {
@@ -52,28 +271,100 @@ pub enum Qos {
 #[serde(rename_all = "camelCase")]
 /// Represents user-defined options common to all Widgets
 pub struct Common {
+    /// Unique within a project, and stable even if Widgets are reordered or
+    /// renamed; assigned when the Widget is added to the project
+    #[serde(default)]
+    pub id: u64,
     pub name: String,
     pub description: String,
-    pub plug: PlugDefinition,
+    /// None for display-only Widgets (Monitors), which never publish
+    pub plug: Option<PlugDefinition>,
     pub midi_mapping: Option<MidiMapping>,
 
+    // The fields below are the editable values the plug definition is built
+    // from; project files older than format version 1 do not include them,
+    // see `project::migrate_v0_to_v1`
+    #[serde(default)]
+    pub use_custom_topic: bool,
+    #[serde(default)]
+    pub custom_topic: String,
+    #[serde(default)]
+    pub plug_name: String,
+    #[serde(default = "default_qos")]
+    pub qos: Qos,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default = "default_auto_send")]
+    pub auto_send: bool,
+    /// Also subscribe to this Widget's own topic, and update the value
+    /// (without re-publishing) whenever another Agent publishes there
+    #[serde(default)]
+    pub sync_from_network: bool,
+    /// The Page this Widget is shown on; Widgets with an unknown (or zero)
+    /// Page ID are shown on the first Page
+    #[serde(default)]
+    pub page_id: u64,
+    /// Optional Group within the Page
+    #[serde(default)]
+    pub group_id: Option<u64>,
+    /// Where the window was last shown; None until first shown (or after
+    /// the layout is reset), in which case egui places it automatically
+    #[serde(default)]
+    pub window: Option<WindowLayout>,
+    /// How the value is encoded when published (and decoded when synced)
+    #[serde(default)]
+    pub encoding: PayloadEncoding,
+    /// If set, the value can also be published repeatedly, e.g. as a
+    /// heartbeat
+    #[serde(default)]
+    pub repeat: Option<RepeatOptions>,
+
     // The fields below are never used in on-disk versions,
     // only in-memory state
     #[serde(skip)]
     is_edit_mode: bool,
     #[serde(skip)]
-    pub use_custom_topic: bool,
+    sync_plug: Option<PlugDefinition>,
     #[serde(skip)]
-    pub custom_topic: String,
-    #[serde(skip)]
-    pub plug_name: String,
-    #[serde(skip, default = "default_qos")]
-    pub qos: Qos,
-    #[serde(skip)]
-    pub retain: bool,
+    repeat_state: RepeatState,
+}
 
-    #[serde(skip, default = "default_auto_send")]
-    pub auto_send: bool,
+/// Shortest interval for repeated sending, to avoid flooding the broker
+pub const MIN_REPEAT_INTERVAL_MS: u64 = 10;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RepeatOptions {
+    pub interval_ms: u64,
+    /// Saved, so that e.g. a heartbeat starts again when the project is
+    /// loaded; not part of the undo history
+    pub is_running: bool,
+}
+
+#[derive(Default, Debug)]
+struct RepeatState {
+    /// As last passed to the Repeater; None if not running
+    job: Option<RepeatJob>,
+    shared: Arc<RepeatShared>,
+}
+
+/// Stops the background sending if the Widget is removed
+impl Drop for RepeatState {
+    fn drop(&mut self) {
+        self.shared.stop();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// Position (top left corner), content size and collapsed state of a
+/// Widget's window, saved so that the layout is restored on load
+pub struct WindowLayout {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub collapsed: bool,
 }
 
 fn default_auto_send() -> bool {
@@ -84,8 +375,19 @@ fn default_qos() -> Qos {
     Qos::AtLeastOnce
 }
 
-pub fn shortened_name(full_name: &str) -> String {
-    String::from(full_name.replace(' ', "_").trim())
+fn build_output_plug(
+    plug_name: &str,
+    qos: Qos,
+    retain: bool,
+    custom_topic: Option<&str>,
+    agent: &mut TetherAgent,
+) -> PlugDefinition {
+    PlugOptionsBuilder::create_output(plug_name)
+        .qos(Some(qos as i32))
+        .retain(Some(retain))
+        .topic(custom_topic)
+        .build(agent)
+        .expect("failed to create output")
 }
 
 impl Common {
@@ -96,18 +398,8 @@ impl Common {
         custom_topic: Option<&str>,
         agent: &mut TetherAgent,
     ) -> Self {
-        let plug = match custom_topic {
-            Some(topic) => PlugOptionsBuilder::create_output(plug_name)
-                .topic(Some(topic))
-                .build(agent)
-                .expect("failed to create output"),
-
-            None => PlugOptionsBuilder::create_output(plug_name)
-                .build(agent)
-                .expect("failed to create output"),
-        };
-
         Common {
+            id: 0,
             name: String::from(widget_name),
             description: {
                 if let Some(d) = description {
@@ -116,18 +408,129 @@ impl Common {
                     String::from("no description provided")
                 }
             },
-            plug,
+            plug: Some(build_output_plug(
+                plug_name,
+                default_qos(),
+                false,
+                custom_topic,
+                agent,
+            )),
             is_edit_mode: true,
-            plug_name: shortened_name(widget_name),
-            use_custom_topic: false,
+            plug_name: String::from(plug_name),
+            use_custom_topic: custom_topic.is_some(),
             auto_send: true,
             midi_mapping: None,
-            qos: Qos::AtMostOnce,
+            qos: default_qos(),
             retain: false,
-            custom_topic: String::from(""),
+            custom_topic: String::from(custom_topic.unwrap_or("")),
+            sync_from_network: false,
+            page_id: 0,
+            group_id: None,
+            window: None,
+            encoding: PayloadEncoding::default(),
+            repeat: None,
+            sync_plug: None,
+            repeat_state: RepeatState::default(),
         }
     }
 
+    /// For display-only Widgets, which subscribe but never publish, so need
+    /// no output Plug
+    pub fn new_input_only(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+    ) -> Self {
+        Common {
+            id: 0,
+            name: String::from(widget_name),
+            description: String::from(description.unwrap_or("no description provided")),
+            plug: None,
+            is_edit_mode: true,
+            plug_name: String::from(plug_name),
+            use_custom_topic: custom_topic.is_some(),
+            auto_send: false,
+            midi_mapping: None,
+            qos: default_qos(),
+            retain: false,
+            custom_topic: String::from(custom_topic.unwrap_or("")),
+            sync_from_network: false,
+            page_id: 0,
+            group_id: None,
+            window: None,
+            encoding: PayloadEncoding::default(),
+            repeat: None,
+            sync_plug: None,
+            repeat_state: RepeatState::default(),
+        }
+    }
+
+    pub fn is_repeating(&self) -> bool {
+        matches!(self.repeat, Some(repeat) if repeat.is_running)
+    }
+
+    pub fn start_repeat(&mut self) {
+        if let Some(repeat) = &mut self.repeat {
+            repeat.is_running = true;
+        }
+    }
+
+    pub fn stop_repeat(&mut self) {
+        if let Some(repeat) = &mut self.repeat {
+            repeat.is_running = false;
+        }
+    }
+
+    /// Start, update or stop the background sending for this Widget; the
+    /// payload is the current value, encoded, if repeating. Only passed on
+    /// to the Repeater when something has changed.
+    pub fn update_repeat(&mut self, payload: Option<Vec<u8>>, repeater: &Repeater) {
+        let job = match (self.repeat, &self.plug, payload) {
+            (Some(repeat), Some(plug), Some(payload)) if repeat.is_running => Some(RepeatJob {
+                topic: String::from(plug.topic()),
+                qos: self.qos as i32,
+                retain: self.retain,
+                interval: Duration::from_millis(repeat.interval_ms.max(MIN_REPEAT_INTERVAL_MS)),
+                payload,
+            }),
+            _ => None,
+        };
+        if job == self.repeat_state.job {
+            return;
+        }
+        match &job {
+            Some(job) => {
+                if self.repeat_state.job.is_none() {
+                    // Count from zero each time it is started
+                    self.repeat_state = RepeatState::default();
+                }
+                repeater.run(job.clone(), self.repeat_state.shared.clone());
+            }
+            None => self.repeat_state.shared.stop(),
+        }
+        self.repeat_state.job = job;
+    }
+
+    /// Take over the running state (and background sending) of an earlier
+    /// version of this Widget, e.g. from before an undo
+    pub fn keep_repeat(&mut self, previous: &mut Common) {
+        if let (Some(repeat), Some(previous_repeat)) = (&mut self.repeat, previous.repeat) {
+            repeat.is_running = previous_repeat.is_running;
+        }
+        std::mem::swap(&mut self.repeat_state, &mut previous.repeat_state);
+    }
+
+    /// Number of repeated sends since last started
+    pub fn repeat_count(&self) -> u64 {
+        self.repeat_state.shared.sent_count()
+    }
+
+    /// Number of repeated sends which were due but failed since last started
+    pub fn repeat_failures(&self) -> u64 {
+        self.repeat_state.shared.failed_count()
+    }
+
     pub fn is_edit_mode(&self) -> bool {
         self.is_edit_mode
     }
@@ -135,6 +538,121 @@ impl Common {
     pub fn set_edit_mode(&mut self, value: bool) {
         self.is_edit_mode = value
     }
+
+    /// Build a new Plug Definition from the current (editable) plug name,
+    /// topic and publish options; input-only Widgets are left without one
+    pub fn update_plug_definition(&mut self, tether_agent: &mut TetherAgent) {
+        if self.plug.is_none() {
+            return;
+        }
+        debug!("Will update plug definition");
+        debug!("QOS level: {}", self.qos as i32);
+        debug!("Retain: {}", self.retain);
+
+        self.plug = Some(build_output_plug(
+            &self.plug_name,
+            self.qos,
+            self.retain,
+            if self.use_custom_topic {
+                Some(&self.custom_topic)
+            } else {
+                None
+            },
+            tether_agent,
+        ));
+    }
+
+    /// Subscribe (if enabled, and connected) to the same topic the Widget publishes on.
+    /// Call again whenever the Plug Definition changes.
+    pub fn update_sync_plug(&mut self, tether_agent: &mut TetherAgent) {
+        self.sync_plug = match &self.plug {
+            Some(plug) if self.sync_from_network && tether_agent.is_connected() => {
+                match PlugOptionsBuilder::create_input(&self.plug_name)
+                    .topic(Some(plug.topic()))
+                    .qos(Some(self.qos as i32))
+                    .build(tether_agent)
+                {
+                    Ok(plug) => {
+                        debug!("Subscribed to sync from topic \"{}\"", plug.topic());
+                        Some(plug)
+                    }
+                    Err(e) => {
+                        error!("Failed to subscribe for sync: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+    }
+
+    /// Whether subscriptions made for either would be the same
+    fn has_same_subscription(&self, other: &Common) -> bool {
+        self.plug_name == other.plug_name
+            && self.use_custom_topic == other.use_custom_topic
+            && self.custom_topic == other.custom_topic
+            && self.qos == other.qos
+            && self.sync_from_network == other.sync_from_network
+    }
+
+    pub fn should_sync_from(&self, topic: &TetherOrCustomTopic) -> bool {
+        match &self.sync_plug {
+            Some(plug) => plug.matches(topic),
+            None => false,
+        }
+    }
+}
+
+fn value_to_json<T: Serialize>(entry: &impl CustomWidget<T>) -> Option<Value> {
+    serde_json::to_value(entry.value()).ok()
+}
+
+fn value_from_json<T: Serialize + DeserializeOwned>(
+    entry: &mut impl CustomWidget<T>,
+    value: &Value,
+) -> bool {
+    match serde_json::from_value::<T>(value.clone()) {
+        Ok(v) => {
+            *entry.value_mut() = v;
+            true
+        }
+        Err(e) => {
+            warn!(
+                "Value {} does not fit widget \"{}\": {}",
+                value,
+                entry.common().name,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Decode an incoming payload as the Widget's own value type, and apply it
+/// without publishing. Returns true if the value was updated.
+fn sync_value<T: Serialize + DeserializeOwned>(
+    entry: &mut impl CustomWidget<T>,
+    payload: &[u8],
+) -> bool {
+    let decoded = entry
+        .common()
+        .encoding
+        .decode(payload)
+        .and_then(|value| Ok(serde_json::from_value::<T>(value)?));
+    match decoded {
+        Ok(value) => {
+            *entry.value_mut() = value;
+            true
+        }
+        Err(e) => {
+            warn!(
+                "Could not decode payload for sync to widget \"{}\": {}",
+                entry.common().name,
+                e
+            );
+            false
+        }
+    }
 }
 
 pub trait View {
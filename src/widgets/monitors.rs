@@ -0,0 +1,350 @@
+use std::{collections::VecDeque, time::Instant};
+
+use egui::{
+    plot::{Line, Plot, PlotPoints, Points},
+    Color32, ProgressBar, RichText, Sense, Slider, Ui,
+};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tether_agent::{
+    three_part_topic::TetherOrCustomTopic, PlugDefinition, PlugOptionsBuilder, TetherAgent,
+};
+
+use crate::gui::widget_view::{common_in_use_heading, common_payload_encoding};
+
+use super::{Common, CustomWidget, View};
+
+const PLOT_SIZE: f32 = 200.0;
+const LED_RADIUS: f32 = 12.0;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// How the latest value received by a Monitor is displayed
+pub enum MonitorDisplay {
+    Text,
+    Gauge,
+    Led,
+    ColourSwatch,
+    Scatter,
+    HistoryPlot,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// A display-only Widget: never publishes, but subscribes to a topic and
+/// shows the latest value received there
+pub struct MonitorWidget {
+    common: Common,
+    /// The latest decoded message, if any; not saved to disk
+    #[serde(skip)]
+    value: Value,
+    display: MonitorDisplay,
+    range_min: f64,
+    range_max: f64,
+    /// Number of values kept for Scatter and HistoryPlot displays
+    history_length: usize,
+
+    #[serde(skip)]
+    input_plug: Option<PlugDefinition>,
+    /// (time, value) for HistoryPlot, (x, y) for Scatter
+    #[serde(skip)]
+    history: VecDeque<[f64; 2]>,
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+}
+
+impl MonitorWidget {
+    pub fn new(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        display: MonitorDisplay,
+        agent: &mut TetherAgent,
+    ) -> Self {
+        let mut monitor = MonitorWidget {
+            common: Common::new_input_only(widget_name, description, plug_name, custom_topic),
+            value: Value::Null,
+            display,
+            range_min: 0.,
+            range_max: 1.,
+            history_length: 200,
+            input_plug: None,
+            history: VecDeque::new(),
+            started: Instant::now(),
+        };
+        monitor.subscribe(agent);
+        monitor
+    }
+
+    /// (Re)subscribe using the plug name (from any Agent), or custom topic if set;
+    /// only possible once the Agent is connected
+    pub fn subscribe(&mut self, tether_agent: &mut TetherAgent) {
+        if !tether_agent.is_connected() {
+            self.input_plug = None;
+            return;
+        }
+        let common = &self.common;
+        match PlugOptionsBuilder::create_input(&common.plug_name)
+            .topic(if common.use_custom_topic {
+                Some(&common.custom_topic)
+            } else {
+                None
+            })
+            .build(tether_agent)
+        {
+            Ok(plug) => {
+                debug!("Monitor subscribed to \"{}\"", plug.topic());
+                self.input_plug = Some(plug);
+            }
+            Err(e) => {
+                error!("Failed to subscribe Monitor: {}", e);
+                self.input_plug = None;
+            }
+        }
+    }
+
+    /// Take over the subscription of an earlier version of this Monitor
+    pub fn keep_subscription(&mut self, previous: &mut MonitorWidget) {
+        self.input_plug = previous.input_plug.take();
+    }
+
+    /// Decode and store a message, if it matches this Monitor's subscription.
+    /// Returns true if the value was updated.
+    pub fn receive(&mut self, topic: &TetherOrCustomTopic, payload: &[u8]) -> bool {
+        match &self.input_plug {
+            Some(plug) if plug.matches(topic) => {}
+            _ => return false,
+        }
+        let value = match self.common.encoding.decode(payload) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Monitor could not decode payload: {}", e);
+                return false;
+            }
+        };
+        let sample = match self.display {
+            MonitorDisplay::HistoryPlot => {
+                as_number(&value).map(|y| [self.started.elapsed().as_secs_f64(), y])
+            }
+            MonitorDisplay::Scatter => as_point(&value),
+            _ => None,
+        };
+        if let Some(sample) = sample {
+            self.history.push_back(sample);
+            while self.history.len() > self.history_length {
+                self.history.pop_front();
+            }
+        }
+        self.value = value;
+        true
+    }
+
+    fn render_value(&mut self, ui: &mut Ui) {
+        if self.value.is_null() {
+            ui.label(RichText::new("No value received yet").color(Color32::DARK_GRAY));
+        }
+        match self.display {
+            MonitorDisplay::Text => {
+                ui.label(RichText::new(self.value.to_string()).monospace());
+            }
+            MonitorDisplay::Gauge => {
+                let value = as_number(&self.value).unwrap_or(self.range_min);
+                let span = self.range_max - self.range_min;
+                let progress = if span == 0. {
+                    0.
+                } else {
+                    ((value - self.range_min) / span).clamp(0., 1.)
+                };
+                ui.add(ProgressBar::new(progress as f32).text(format!("{:.3}", value)));
+                ui.small(format!("Range: {}-{}", self.range_min, self.range_max));
+            }
+            MonitorDisplay::Led => {
+                let is_on = as_bool(&self.value);
+                ui.horizontal(|ui| {
+                    let (rect, _response) = ui.allocate_exact_size(
+                        egui::vec2(LED_RADIUS * 2., LED_RADIUS * 2.),
+                        Sense::hover(),
+                    );
+                    ui.painter().circle_filled(
+                        rect.center(),
+                        LED_RADIUS,
+                        if is_on {
+                            Color32::GREEN
+                        } else {
+                            Color32::DARK_GRAY
+                        },
+                    );
+                    ui.label(if is_on { "ON" } else { "OFF" });
+                });
+            }
+            MonitorDisplay::ColourSwatch => {
+                let (rect, _response) =
+                    ui.allocate_exact_size(egui::vec2(PLOT_SIZE, PLOT_SIZE / 4.), Sense::hover());
+                ui.painter()
+                    .rect_filled(rect, 4.0, as_colour(&self.value).unwrap_or_default());
+            }
+            MonitorDisplay::Scatter => {
+                let points: Vec<[f64; 2]> = self.history.iter().copied().collect();
+                let latest = as_point(&self.value);
+                Plot::new("monitor_scatter")
+                    .width(PLOT_SIZE)
+                    .height(PLOT_SIZE)
+                    .data_aspect(1.0)
+                    .show(ui, |plot_ui| {
+                        plot_ui.points(Points::new(points).color(Color32::DARK_GRAY));
+                        if let Some(p) = latest {
+                            plot_ui.points(Points::new(vec![p]).radius(4.0));
+                        }
+                    });
+            }
+            MonitorDisplay::HistoryPlot => {
+                ui.label(format!(
+                    "Latest: {}",
+                    as_number(&self.value).unwrap_or_default()
+                ));
+                let line = Line::new(PlotPoints::from_iter(self.history.iter().copied()));
+                Plot::new("monitor_history")
+                    .width(PLOT_SIZE * 1.5)
+                    .height(PLOT_SIZE)
+                    .include_y(self.range_min)
+                    .include_y(self.range_max)
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(line);
+                    });
+            }
+        }
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(if *b { 1. } else { 0. }),
+        _ => None,
+    }
+}
+
+fn as_bool(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().unwrap_or_default() != 0.,
+        _ => false,
+    }
+}
+
+/// Accepts `[x, y]` or `{x, y}`
+fn as_point(value: &Value) -> Option<[f64; 2]> {
+    match value {
+        Value::Array(a) if a.len() >= 2 => Some([as_number(&a[0])?, as_number(&a[1])?]),
+        Value::Object(o) => Some([as_number(o.get("x")?)?, as_number(o.get("y")?)?]),
+        _ => None,
+    }
+}
+
+/// Accepts `[r, g, b]` or `[r, g, b, a]`, either as 0-255 integers or 0-1 floats
+fn as_colour(value: &Value) -> Option<Color32> {
+    let channels = value
+        .as_array()?
+        .iter()
+        .map(as_number)
+        .collect::<Option<Vec<f64>>>()?;
+    if channels.len() < 3 {
+        return None;
+    }
+    let is_normalised = value
+        .as_array()?
+        .iter()
+        .any(|c| c.is_f64() && c.as_f64().unwrap_or_default() <= 1.0);
+    let to_u8 = |c: f64| {
+        if is_normalised {
+            (c * 255.).round() as u8
+        } else {
+            c as u8
+        }
+    };
+    let alpha = channels.get(3).copied().map(to_u8).unwrap_or(255);
+    Some(Color32::from_rgba_unmultiplied(
+        to_u8(channels[0]),
+        to_u8(channels[1]),
+        to_u8(channels[2]),
+        alpha,
+    ))
+}
+
+impl CustomWidget<Value> for MonitorWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &Value {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut Value {
+        &mut self.value
+    }
+}
+
+impl View for MonitorWidget {
+    fn render_in_use(&mut self, ui: &mut Ui, _tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+        self.render_value(ui);
+        ui.label(
+            RichText::new(format!(
+                "Listening: {}",
+                match &self.input_plug {
+                    Some(plug) => plug.topic(),
+                    None => "(not subscribed)",
+                }
+            ))
+            .color(Color32::LIGHT_BLUE),
+        );
+    }
+
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        ui.label("Widget Name");
+        ui.text_edit_singleline(&mut self.common.name);
+
+        ui.label("Description");
+        ui.text_edit_multiline(&mut self.common.description);
+
+        ui.label("Plug Name");
+        ui.text_edit_singleline(&mut self.common.plug_name);
+
+        ui.checkbox(&mut self.common.use_custom_topic, "Use custom topic");
+        ui.add_enabled_ui(self.common.use_custom_topic, |ui| {
+            ui.text_edit_singleline(&mut self.common.custom_topic);
+        });
+
+        common_payload_encoding(ui, &mut self.common.encoding);
+
+        ui.label("Display");
+        ui.horizontal_wrapped(|ui| {
+            ui.radio_value(&mut self.display, MonitorDisplay::Text, "Text");
+            ui.radio_value(&mut self.display, MonitorDisplay::Gauge, "Gauge");
+            ui.radio_value(&mut self.display, MonitorDisplay::Led, "LED");
+            ui.radio_value(&mut self.display, MonitorDisplay::ColourSwatch, "Colour");
+            ui.radio_value(&mut self.display, MonitorDisplay::Scatter, "Scatter");
+            ui.radio_value(&mut self.display, MonitorDisplay::HistoryPlot, "Plot");
+        });
+
+        ui.collapsing("Range", |ui| {
+            ui.label("Min");
+            ui.add(egui::DragValue::new(&mut self.range_min).speed(0.1));
+            ui.label("Max");
+            ui.add(egui::DragValue::new(&mut self.range_max).speed(0.1));
+            ui.label("History length");
+            ui.add(Slider::new(&mut self.history_length, 2..=2000));
+        });
+
+        if ui.button("Save").clicked() {
+            self.history.clear();
+            self.subscribe(tether_agent);
+            self.common.set_edit_mode(false);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use egui::{DragValue, Slider, Ui};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::ops::RangeInclusive;
+use tether_agent::TetherAgent;
+
+use crate::{
+    gui::widget_view::{
+        common_editable_values, common_in_use_heading, common_save_button, common_send_button,
+        publish_value,
+    },
+    midi_mapping::MidiMapping,
+};
+
+use super::{response_curve::ResponseCurve, rng::Rng, Common, CustomWidget, View};
+
+const MAX_LENGTH: usize = 512;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Publishes an array of numbers, one slider per element, e.g. for LED
+/// strips or banks of motors
+pub struct NumberArrayWidget {
+    common: Common,
+    value: Vec<f64>,
+    range_min: f64,
+    range_max: f64,
+    /// If false, each element uses its own range from `element_ranges`
+    shared_range: bool,
+    element_ranges: Vec<[f64; 2]>,
+    should_round: bool,
+    /// Optional MIDI CC mapping for each element
+    element_midi: Vec<Option<MidiMapping>>,
+    /// Applied to MIDI CC values, within each element's range
+    #[serde(default)]
+    response_curve: ResponseCurve,
+    #[serde(skip)]
+    set_all_value: f64,
+    #[serde(skip, default = "Rng::from_time")]
+    rng: Rng,
+}
+
+#[allow(clippy::too_many_arguments)]
+impl NumberArrayWidget {
+    pub fn new(
+        name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        length: usize,
+        range: RangeInclusive<f64>,
+        round_off: bool,
+        agent: &mut TetherAgent,
+    ) -> Self {
+        let mut widget = NumberArrayWidget {
+            common: Common::new(name, description, plug_name, custom_topic, agent),
+            value: vec![*range.start(); length],
+            range_min: *range.start(),
+            range_max: *range.end(),
+            shared_range: true,
+            element_ranges: Vec::new(),
+            should_round: round_off,
+            element_midi: Vec::new(),
+            response_curve: ResponseCurve::default(),
+            set_all_value: *range.start(),
+            rng: Rng::from_time(),
+        };
+        widget.match_lengths();
+        widget
+    }
+
+    /// Keep the per-element settings the same length as the value, which
+    /// may have been changed by a Preset or the network
+    fn match_lengths(&mut self) {
+        let length = self.value.len();
+        let shared = [self.range_min, self.range_max];
+        self.element_ranges.resize(length, shared);
+        self.element_midi.resize_with(length, || None);
+    }
+
+    fn set_length(&mut self, length: usize) {
+        let fill = self.value.last().copied().unwrap_or(self.range_min);
+        self.value.resize(length, fill);
+        self.match_lengths();
+    }
+
+    /// Swapped if necessary, e.g. for project files edited by hand
+    pub fn element_range(&self, index: usize) -> RangeInclusive<f64> {
+        let (min, max) = match self.element_ranges.get(index) {
+            Some([min, max]) if !self.shared_range => (*min, *max),
+            _ => (self.range_min, self.range_max),
+        };
+        min.min(max)..=min.max(max)
+    }
+
+    pub fn should_round(&self) -> bool {
+        self.should_round
+    }
+
+    /// The value at a position (0-1) along the response curve, within the
+    /// element's range, e.g. from a MIDI CC
+    pub fn element_value_at_position(&self, index: usize, position: f64) -> f64 {
+        self.response_curve
+            .to_value(position, self.element_range(index))
+    }
+
+    pub fn element_midi_mut(&mut self) -> &mut Vec<Option<MidiMapping>> {
+        self.match_lengths();
+        &mut self.element_midi
+    }
+
+    /// Set one element, rounding if needed
+    pub fn set_element(&mut self, index: usize, value: f64) {
+        let value = if self.should_round {
+            value.round()
+        } else {
+            value
+        };
+        if let Some(element) = self.value.get_mut(index) {
+            *element = value;
+        }
+    }
+
+    fn set_all(&mut self, value: f64) {
+        for i in 0..self.value.len() {
+            let range = self.element_range(i);
+            self.set_element(i, value.clamp(*range.start(), *range.end()));
+        }
+    }
+
+    /// From the start of each element's range (first element) to the end
+    /// (last element)
+    fn ramp(&mut self) {
+        let last = self.value.len().saturating_sub(1).max(1) as f64;
+        for i in 0..self.value.len() {
+            let range = self.element_range(i);
+            let t = i as f64 / last;
+            self.set_element(i, range.start() + (range.end() - range.start()) * t);
+        }
+    }
+
+    fn randomise(&mut self) {
+        for i in 0..self.value.len() {
+            let range = self.element_range(i);
+            let t = self.rng.next_f64();
+            self.set_element(i, range.start() + (range.end() - range.start()) * t);
+        }
+    }
+
+    /// The whole array as one list; whole numbers if rounding
+    pub fn output(&self) -> Value {
+        if self.should_round {
+            let rounded: Vec<i64> = self.value.iter().map(|v| v.round() as i64).collect();
+            json!(rounded)
+        } else {
+            json!(self.value)
+        }
+    }
+
+    pub fn send(&self, tether_agent: &TetherAgent) {
+        publish_value(self.common(), &self.output(), tether_agent);
+    }
+}
+
+impl CustomWidget<Vec<f64>> for NumberArrayWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &Vec<f64> {
+        &self.value
+    }
+    fn value_mut(&mut self) -> &mut Vec<f64> {
+        &mut self.value
+    }
+}
+
+impl View for NumberArrayWidget {
+    fn render_in_use(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+        self.match_lengths();
+
+        if let Some(midi) = &self.common().midi_mapping {
+            match midi {
+                MidiMapping::Learning => {}
+                MidiMapping::Set(mapping) => {
+                    ui.label(format!(
+                        "MIDI mapped: send on ch {} note {}",
+                        mapping.channel, mapping.controller_or_note
+                    ));
+                }
+            }
+        }
+
+        let mut changed = false;
+        let step = if self.should_round { 1.0 } else { 0.0 };
+        for i in 0..self.value.len() {
+            let range = self.element_range(i);
+            let cc = match &self.element_midi[i] {
+                Some(MidiMapping::Set(mapping)) => format!(" (cc {})", mapping.controller_or_note),
+                _ => String::new(),
+            };
+            if ui
+                .add(
+                    Slider::new(&mut self.value[i], range)
+                        .step_by(step)
+                        .text(format!("{}{}", i, cc)),
+                )
+                .changed()
+            {
+                changed = true;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            let (min, max) = (self.range_min, self.range_max);
+            ui.add(
+                DragValue::new(&mut self.set_all_value).clamp_range(min.min(max)..=min.max(max)),
+            );
+            if ui.button("Set all").clicked() {
+                self.set_all(self.set_all_value);
+                changed = true;
+            }
+            if ui.button("Ramp").clicked() {
+                self.ramp();
+                changed = true;
+            }
+            if ui.button("Randomise").clicked() {
+                self.randomise();
+                changed = true;
+            }
+        });
+
+        if changed && self.common().auto_send || common_send_button(ui, self, true).clicked() {
+            self.send(tether_agent);
+        }
+    }
+
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        common_editable_values(ui, self, tether_agent);
+        self.match_lengths();
+
+        ui.horizontal(|ui| {
+            ui.label("Length");
+            let mut length = self.value.len();
+            if ui
+                .add(DragValue::new(&mut length).clamp_range(1..=MAX_LENGTH))
+                .changed()
+            {
+                self.set_length(length);
+            }
+        });
+        ui.checkbox(&mut self.should_round, "Round to whole numbers");
+
+        ui.collapsing("Range", |ui| {
+            ui.checkbox(&mut self.shared_range, "Same range for all elements");
+            egui::Grid::new(ui.id().with("ranges"))
+                .num_columns(3)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("Min");
+                    ui.label("Max");
+                    ui.end_row();
+                    if self.shared_range {
+                        ui.label("All");
+                        ui.add(
+                            DragValue::new(&mut self.range_min)
+                                .clamp_range(f64::NEG_INFINITY..=self.range_max)
+                                .speed(0.1),
+                        );
+                        ui.add(
+                            DragValue::new(&mut self.range_max)
+                                .clamp_range(self.range_min..=f64::INFINITY)
+                                .speed(0.1),
+                        );
+                        ui.end_row();
+                    } else {
+                        for (i, [min, max]) in self.element_ranges.iter_mut().enumerate() {
+                            ui.label(i.to_string());
+                            ui.add(
+                                DragValue::new(min)
+                                    .clamp_range(f64::NEG_INFINITY..=*max)
+                                    .speed(0.1),
+                            );
+                            ui.add(
+                                DragValue::new(max)
+                                    .clamp_range(*min..=f64::INFINITY)
+                                    .speed(0.1),
+                            );
+                            ui.end_row();
+                        }
+                    }
+                });
+        });
+
+        ui.collapsing("MIDI CC per element", |ui| {
+            egui::Grid::new(ui.id().with("element_midi"))
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (i, midi) in self.element_midi.iter_mut().enumerate() {
+                        ui.label(i.to_string());
+                        match midi {
+                            None => ui.label("-"),
+                            Some(MidiMapping::Learning) => ui.label("Learning..."),
+                            Some(MidiMapping::Set(mapping)) => ui.label(format!(
+                                "ch {} cc {}",
+                                mapping.channel, mapping.controller_or_note
+                            )),
+                        };
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Learn").clicked() {
+                                *midi = Some(MidiMapping::Learning);
+                            }
+                            if midi.is_some() && ui.small_button("Clear").clicked() {
+                                *midi = None;
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+            ui.collapsing("Response curve", |ui| {
+                self.response_curve.render_editor(ui);
+            });
+        });
+
+        common_save_button(ui, self, tether_agent);
+    }
+}
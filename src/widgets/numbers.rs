@@ -1,21 +1,51 @@
-use egui::{Slider, Ui};
+use egui::{emath::remap_clamp, pos2, vec2, DragValue, FontId, Sense, Shape, Slider, Stroke, Ui};
 use serde::{Deserialize, Serialize};
-use std::ops::RangeInclusive;
+use std::{f32::consts::PI, ops::RangeInclusive};
 use tether_agent::TetherAgent;
 
 use crate::{
     gui::widget_view::{
         common_editable_values, common_in_use_heading, common_save_button, common_send,
-        common_send_button,
+        common_send_button, publish_value,
     },
     midi_mapping::MidiMapping,
 };
 
-use super::{Common, CustomWidget, View};
+use super::{response_curve::ResponseCurve, Common, CustomWidget, View};
 
 const SENSIBLE_MIN: f64 = -100000.;
 const SENSIBLE_MAX: f64 = 100000.;
 
+const KNOB_SIZE: f32 = 64.0;
+/// The knob turns from -KNOB_SWEEP to +KNOB_SWEEP (radians from the top)
+const KNOB_SWEEP: f32 = PI * 0.75;
+const FADER_HEIGHT: f32 = 160.0;
+const PAD_SIZE: f32 = 160.0;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// How the value is shown (and changed) when in use
+pub enum NumberStyle {
+    Slider,
+    /// Rotary knob; drag up/down (or left/right) to turn
+    Knob,
+    /// Tall vertical slider
+    Fader,
+    /// Large number, dragged or typed in
+    DragValue,
+    /// This number on X, and another Number Widget on Y
+    XYPad,
+}
+
+#[derive(Clone)]
+/// Another Number Widget in the project, which an XY pad can be paired with
+pub struct NumberSummary {
+    pub id: u64,
+    pub name: String,
+    pub value: f64,
+    pub range: RangeInclusive<f64>,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NumberWidget {
@@ -26,12 +56,30 @@ pub struct NumberWidget {
     should_round: bool,
     #[serde(default = "default_step_size")]
     step_size: f64,
+    /// Applied to the slider, fader and knob, and to MIDI CC values
+    #[serde(default)]
+    response_curve: ResponseCurve,
+    #[serde(default = "default_style")]
+    style: NumberStyle,
+    /// The Number Widget on the Y axis of the XY pad
+    #[serde(default)]
+    paired_id: Option<u64>,
+    /// The other Number Widgets, refreshed every frame
+    #[serde(skip)]
+    others: Vec<NumberSummary>,
+    /// A new value for the paired Widget, set from the XY pad
+    #[serde(skip)]
+    paired_change: Option<f64>,
 }
 
 fn default_step_size() -> f64 {
     1.0
 }
 
+fn default_style() -> NumberStyle {
+    NumberStyle::Slider
+}
+
 #[allow(clippy::too_many_arguments)]
 impl NumberWidget {
     pub fn new(
@@ -57,6 +105,11 @@ impl NumberWidget {
                     (*range.start() - *range.end()).abs() / 100.
                 }
             },
+            response_curve: ResponseCurve::default(),
+            style: NumberStyle::Slider,
+            paired_id: None,
+            others: Vec::new(),
+            paired_change: None,
         }
     }
 
@@ -67,6 +120,218 @@ impl NumberWidget {
     pub fn should_round(&self) -> bool {
         self.should_round
     }
+
+    /// Keep a value received from the network within the range, and
+    /// rounded if needed, as for MIDI CC values
+    pub fn constrain_value(&mut self) {
+        let (min, max) = (
+            self.range_min.min(self.range_max),
+            self.range_min.max(self.range_max),
+        );
+        let value = self.value.clamp(min, max);
+        self.value = if self.should_round {
+            value.round()
+        } else {
+            value
+        };
+    }
+
+    /// Position (0-1) of the current value along the response curve
+    pub fn position(&self) -> f64 {
+        self.response_curve.to_position(self.value, self.range())
+    }
+
+    /// The value at a position (0-1) along the response curve, e.g. from a
+    /// MIDI CC value
+    pub fn value_at_position(&self, position: f64) -> f64 {
+        self.response_curve.to_value(position, self.range())
+    }
+
+    pub fn summary(&self) -> NumberSummary {
+        NumberSummary {
+            id: self.common.id,
+            name: self.common.name.clone(),
+            value: self.value,
+            range: self.range(),
+        }
+    }
+
+    /// Other Number Widgets, for pairing with an XY pad
+    pub fn set_others(&mut self, others: Vec<NumberSummary>) {
+        self.others = others;
+    }
+
+    /// The paired Widget's ID and new value, if changed via the XY pad
+    pub fn take_paired_change(&mut self) -> Option<(u64, f64)> {
+        match (self.paired_id, self.paired_change.take()) {
+            (Some(id), Some(value)) => Some((id, value)),
+            _ => None,
+        }
+    }
+
+    /// Set the value, as if changed by hand, sending if auto send is on.
+    /// Whole numbers are rounded whether or not they are sent now, since the
+    /// knob, fader and XY pad are not stepped.
+    pub fn apply_change(&mut self, value: f64, tether_agent: &TetherAgent) {
+        self.value = if self.should_round {
+            value.round()
+        } else {
+            value
+        };
+        if !self.common().auto_send {
+            return;
+        }
+        if self.should_round {
+            // Make sure it is published as an integer, using the Widget's
+            // payload encoding
+            publish_value(self.common(), &(*self.value() as i64), tether_agent);
+        } else {
+            // No rounding, just encode and publish
+            common_send(self, tether_agent);
+        }
+    }
+
+    /// Returns true if changed
+    fn render_knob(&mut self, ui: &mut Ui) -> bool {
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(KNOB_SIZE, KNOB_SIZE), Sense::click_and_drag());
+        let mut changed = false;
+        let mut position = self.position();
+        // The unrounded position is kept while dragging, otherwise small
+        // movements of a whole number knob would be rounded away every frame
+        let drag_id = response.id.with("position");
+        if response.dragged() {
+            let delta = response.drag_delta();
+            let steps = (delta.x - delta.y) as f64;
+            let start = ui
+                .data_mut(|d| d.get_temp::<f64>(drag_id))
+                .unwrap_or(position);
+            position = (start + steps / 200.).clamp(0., 1.);
+            ui.data_mut(|d| d.insert_temp(drag_id, position));
+            self.value = self.value_at_position(position);
+            changed = true;
+        }
+        if response.drag_released() {
+            ui.data_mut(|d| d.remove::<f64>(drag_id));
+        }
+
+        let t = remap_clamp(position, 0. ..=1., -1. ..=1.) as f32;
+        let angle = t * KNOB_SWEEP;
+        let centre = rect.center();
+        let radius = rect.width() / 2. - 2.;
+        let visuals = ui.style().interact(&response);
+        let painter = ui.painter();
+        painter.circle(centre, radius, visuals.bg_fill, visuals.bg_stroke);
+        // The track, from the lowest to the current value
+        let track: Vec<_> = (0..=32)
+            .map(|i| {
+                let a = -KNOB_SWEEP + (angle + KNOB_SWEEP) * i as f32 / 32.;
+                centre + vec2(a.sin(), -a.cos()) * (radius - 4.)
+            })
+            .collect();
+        painter.add(Shape::line(
+            track,
+            Stroke::new(3.0, ui.visuals().selection.bg_fill),
+        ));
+        painter.line_segment(
+            [
+                centre,
+                centre + vec2(angle.sin(), -angle.cos()) * (radius - 4.),
+            ],
+            Stroke::new(2.0, visuals.fg_stroke.color),
+        );
+        ui.label(format!("{:.3}", self.value));
+        changed
+    }
+
+    /// A slider (or fader) along the response curve, showing the value
+    /// rather than the position; returns true if changed
+    fn render_curved_slider(&self, ui: &mut Ui, value: &mut f64) -> bool {
+        let mut position = self.position();
+        let curve = &self.response_curve;
+        let range = self.range();
+        let vertical = self.style == NumberStyle::Fader;
+        let changed = ui
+            .scope(|ui| {
+                let mut slider = Slider::new(&mut position, 0. ..=1.)
+                    .custom_formatter(|p, _| format!("{:.3}", curve.to_value(p, range.clone())))
+                    .custom_parser(|text| {
+                        text.parse::<f64>()
+                            .ok()
+                            .map(|v| curve.to_position(v, range.clone()))
+                    });
+                if vertical {
+                    ui.spacing_mut().slider_width = FADER_HEIGHT;
+                    slider = slider.vertical();
+                }
+                ui.add(slider).changed()
+            })
+            .inner;
+        if changed {
+            // Not stepped, which would undo the fine control a curve gives
+            *value = self.value_at_position(position);
+        }
+        changed
+    }
+
+    /// Returns true if this Widget's value changed; a change to the paired
+    /// Widget is kept for `take_paired_change`
+    fn render_xy_pad(&mut self, ui: &mut Ui) -> bool {
+        let paired = self
+            .paired_id
+            .and_then(|id| self.others.iter().find(|o| o.id == id))
+            .cloned();
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(PAD_SIZE, PAD_SIZE), Sense::click_and_drag());
+        let (min, max) = (self.range_min, self.range_max);
+        let x_range = rect.left() as f64..=rect.right() as f64;
+        // Screen Y goes down, but values go up
+        let y_range = rect.bottom() as f64..=rect.top() as f64;
+
+        let mut changed = false;
+        if response.dragged() || response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                self.value = remap_clamp(pos.x as f64, x_range.clone(), min..=max);
+                changed = true;
+                if let Some(paired) = &paired {
+                    self.paired_change = Some(remap_clamp(
+                        pos.y as f64,
+                        y_range.clone(),
+                        paired.range.clone(),
+                    ));
+                }
+            }
+        }
+
+        let y_value = self
+            .paired_change
+            .or(paired.as_ref().map(|p| p.value))
+            .unwrap_or_default();
+        let x = remap_clamp(self.value, min..=max, x_range) as f32;
+        let y = match &paired {
+            Some(p) => remap_clamp(y_value, p.range.clone(), y_range) as f32,
+            None => rect.center().y,
+        };
+        let visuals = ui.style().interact(&response);
+        let painter = ui.painter();
+        painter.rect(rect, 2.0, visuals.bg_fill, visuals.bg_stroke);
+        let guide = Stroke::new(1.0, visuals.bg_stroke.color);
+        painter.hline(rect.x_range(), y, guide);
+        painter.vline(x, rect.y_range(), guide);
+        painter.circle_filled(pos2(x, y), 6.0, ui.visuals().selection.bg_fill);
+
+        match &paired {
+            Some(p) => ui.small(format!(
+                "X: {:.3}, Y ({}): {:.3}",
+                self.value, p.name, y_value
+            )),
+            None => ui.small(format!(
+                "X: {:.3}; choose a Widget for Y while editing",
+                self.value
+            )),
+        };
+        changed
+    }
 }
 
 impl CustomWidget<f64> for NumberWidget {
@@ -91,29 +356,57 @@ impl View for NumberWidget {
         let &min = self.range().start();
         let &max = self.range().end();
 
-        if ui
-            .add(
-                Slider::new(&mut self.value, min..=max)
-                    .clamp_to_range(false)
-                    .step_by(self.step_size),
-            )
-            .changed()
-            && self.common().auto_send
-        {
-            if self.should_round {
-                // Round off the value (internally f64)
-                *self.value_mut() = self.value().round();
-                // Make sure we convert to integer explicity before sending
-                let value = *self.value() as i64;
-                let payload = rmp_serde::to_vec(&value).expect("failed to serialised");
-                tether_agent
-                    .publish(&self.common().plug, Some(&payload))
-                    .expect("failed to publish");
-            } else {
-                // No rounding, just encode and publish
-                common_send(self, tether_agent);
+        let mut value = self.value;
+        let changed = match self.style {
+            NumberStyle::Slider | NumberStyle::Fader if !self.response_curve.is_linear() => {
+                self.render_curved_slider(ui, &mut value)
+            }
+            NumberStyle::Slider => ui
+                .add(
+                    Slider::new(&mut value, min..=max)
+                        .clamp_to_range(false)
+                        .step_by(self.step_size),
+                )
+                .changed(),
+            NumberStyle::Fader => {
+                let step_size = self.step_size;
+                ui.scope(|ui| {
+                    ui.spacing_mut().slider_width = FADER_HEIGHT;
+                    ui.add(
+                        Slider::new(&mut value, min..=max)
+                            .vertical()
+                            .step_by(step_size),
+                    )
+                    .changed()
+                })
+                .inner
+            }
+            NumberStyle::DragValue => {
+                ui.scope(|ui| {
+                    ui.style_mut().override_font_id = Some(FontId::proportional(32.));
+                    ui.add(
+                        DragValue::new(&mut value)
+                            .clamp_range(min..=max)
+                            .speed((max - min) / 200.),
+                    )
+                    .changed()
+                })
+                .inner
+            }
+            NumberStyle::Knob => {
+                let changed = self.render_knob(ui);
+                value = self.value;
+                changed
+            }
+            NumberStyle::XYPad => {
+                let changed = self.render_xy_pad(ui);
+                value = self.value;
+                changed
             }
         };
+        if changed {
+            self.apply_change(value, tether_agent);
+        }
         ui.horizontal(|ui| {
             ui.small(format!(
                 "Range: {}-{}",
@@ -164,6 +457,36 @@ impl View for NumberWidget {
             ui.add(Slider::new(&mut self.step_size, 0.0..=max_step));
         });
 
+        ui.collapsing("Response curve", |ui| {
+            self.response_curve.render_editor(ui);
+        });
+
+        ui.label("Style");
+        ui.horizontal_wrapped(|ui| {
+            ui.radio_value(&mut self.style, NumberStyle::Slider, "Slider");
+            ui.radio_value(&mut self.style, NumberStyle::Knob, "Knob");
+            ui.radio_value(&mut self.style, NumberStyle::Fader, "Fader");
+            ui.radio_value(&mut self.style, NumberStyle::DragValue, "Big number");
+            ui.radio_value(&mut self.style, NumberStyle::XYPad, "XY pad");
+        });
+        if self.style == NumberStyle::XYPad {
+            let selected = self
+                .paired_id
+                .and_then(|id| self.others.iter().find(|o| o.id == id))
+                .map_or_else(|| String::from("(none)"), |o| o.name.clone());
+            ui.horizontal(|ui| {
+                ui.label("Y axis");
+                egui::ComboBox::from_id_source(ui.id().with("paired"))
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.paired_id, None, "(none)");
+                        for other in &self.others {
+                            ui.selectable_value(&mut self.paired_id, Some(other.id), &other.name);
+                        }
+                    });
+            });
+        }
+
         common_save_button(ui, self, tether_agent);
     }
 }
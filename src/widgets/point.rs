@@ -1,14 +1,15 @@
 use egui::{
-    plot::{Plot, PlotPoint},
-    Ui,
+    plot::{Line, Plot, PlotPoint, PlotPoints, Points},
+    DragValue, Ui,
 };
-use log::{debug, error};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use tether_agent::TetherAgent;
 
 use crate::{
     gui::widget_view::{
         common_editable_values, common_in_use_heading, common_save_button, common_send_button,
+        publish_value,
     },
     midi_mapping::MidiMapping,
 };
@@ -17,11 +18,62 @@ use super::{Common, CustomWidget, View};
 
 type Point2D = [f64; 2];
 
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PointInputMode {
+    /// Follow the pointer while hovering over the plot (and auto-sending)
+    Hover,
+    /// Only move a handle by clicking or dragging, within the bounds
+    Drag,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PointOutput {
+    /// `[x, y]`
+    Array,
+    /// `{x, y}`
+    Map,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Point2DWidget {
     common: Common,
+    /// In plot coordinates, i.e. before any normalisation or inversion
     value: Point2D,
+    // Projects saved before these options existed get the original
+    // (hover, unbounded) behaviour
+    #[serde(default = "default_mode")]
+    mode: PointInputMode,
+    #[serde(default = "default_min")]
+    min: Point2D,
+    #[serde(default = "default_max")]
+    max: Point2D,
+    /// Publish 0..1 within the bounds
+    #[serde(default)]
+    normalise: bool,
+    /// Publish Y increasing downwards, as on screen
+    #[serde(default)]
+    invert_y: bool,
+    #[serde(default = "default_output")]
+    output: PointOutput,
+}
+
+fn default_mode() -> PointInputMode {
+    PointInputMode::Hover
+}
+
+fn default_min() -> Point2D {
+    [0., 0.]
+}
+
+fn default_max() -> Point2D {
+    [1., 1.]
+}
+
+fn default_output() -> PointOutput {
+    PointOutput::Array
 }
 
 impl Point2DWidget {
@@ -34,9 +86,140 @@ impl Point2DWidget {
     ) -> Self {
         Point2DWidget {
             common: Common::new(widget_name, description, plug_name, custom_topic, agent),
-            value: [0., 0.],
+            value: [0.5, 0.5],
+            mode: PointInputMode::Drag,
+            min: default_min(),
+            max: default_max(),
+            normalise: false,
+            invert_y: false,
+            output: default_output(),
         }
     }
+
+    /// Size of the bounds on either axis, never zero
+    fn span(&self, axis: usize) -> f64 {
+        let span = self.max[axis] - self.min[axis];
+        if span == 0. {
+            1.
+        } else {
+            span
+        }
+    }
+
+    /// Min and max on each axis, swapped if necessary, e.g. for project
+    /// files edited by hand
+    fn bounds(&self) -> (Point2D, Point2D) {
+        (
+            [self.min[0].min(self.max[0]), self.min[1].min(self.max[1])],
+            [self.min[0].max(self.max[0]), self.min[1].max(self.max[1])],
+        )
+    }
+
+    /// The value as published, after normalisation and inversion
+    pub fn output(&self) -> Value {
+        let [mut x, mut y] = self.value;
+        if self.normalise {
+            x = (x - self.min[0]) / self.span(0);
+            y = (y - self.min[1]) / self.span(1);
+        }
+        if self.invert_y {
+            y = if self.normalise {
+                1. - y
+            } else {
+                self.max[1] - (y - self.min[1])
+            };
+        }
+        match self.output {
+            PointOutput::Array => json!([x, y]),
+            PointOutput::Map => json!({ "x": x, "y": y }),
+        }
+    }
+
+    /// The reverse of `output`; accepts either `[x, y]` or `{x, y}`.
+    /// Returns true if the value was valid.
+    pub fn set_from_output(&mut self, value: &Value) -> bool {
+        let (x, y) = match value {
+            Value::Array(a) if a.len() == 2 => (a[0].as_f64(), a[1].as_f64()),
+            Value::Object(o) => (
+                o.get("x").and_then(Value::as_f64),
+                o.get("y").and_then(Value::as_f64),
+            ),
+            _ => (None, None),
+        };
+        let (x, mut y) = match (x, y) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return false,
+        };
+        if self.invert_y {
+            y = if self.normalise {
+                1. - y
+            } else {
+                self.max[1] - (y - self.min[1])
+            };
+        }
+        self.value = if self.normalise {
+            [
+                self.min[0] + x * self.span(0),
+                self.min[1] + y * self.span(1),
+            ]
+        } else {
+            [x, y]
+        };
+        true
+    }
+
+    pub fn send(&self, tether_agent: &TetherAgent) {
+        publish_value(self.common(), &self.output(), tether_agent);
+    }
+
+    fn render_draggable(&mut self, ui: &mut Ui) -> bool {
+        let (min, max) = self.bounds();
+        let current = self.value;
+        let bounds = vec![
+            [min[0], min[1]],
+            [max[0], min[1]],
+            [max[0], max[1]],
+            [min[0], max[1]],
+            [min[0], min[1]],
+        ];
+        let plot = Plot::new("draggable_point")
+            .width(PLOT_SIZE)
+            .height(PLOT_SIZE)
+            .data_aspect(1.0)
+            .allow_drag(false)
+            .include_x(min[0])
+            .include_x(max[0])
+            .include_y(min[1])
+            .include_y(max[1])
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(bounds)));
+                plot_ui.points(Points::new(vec![current]).radius(6.0));
+                plot_ui.pointer_coordinate()
+            });
+
+        let mut changed = false;
+        if plot.response.dragged() || plot.response.clicked() {
+            if let Some(PlotPoint { x, y }) = plot.inner {
+                self.value = [x.clamp(min[0], max[0]), y.clamp(min[1], max[1])];
+                changed = true;
+            }
+        }
+        ui.horizontal(|ui| {
+            for (axis, name) in ["X", "Y"].iter().enumerate() {
+                ui.label(*name);
+                let speed = self.span(axis) / 200.;
+                changed |= ui
+                    .add(
+                        DragValue::new(&mut self.value[axis])
+                            .clamp_range(min[axis]..=max[axis])
+                            .speed(speed),
+                    )
+                    .changed();
+            }
+        });
+        ui.small(format!("Output: {}", self.output()));
+        changed
+    }
 }
 
 impl CustomWidget<Point2D> for Point2DWidget {
@@ -60,6 +243,49 @@ const PLOT_SIZE: f32 = 200.0;
 impl View for Point2DWidget {
     fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
         common_editable_values(ui, self, tether_agent);
+
+        ui.label("Input");
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.mode, PointInputMode::Drag, "Drag handle");
+            ui.radio_value(&mut self.mode, PointInputMode::Hover, "Follow pointer");
+        });
+
+        ui.label("Bounds");
+        ui.horizontal(|ui| {
+            ui.label("X");
+            ui.add(
+                DragValue::new(&mut self.min[0])
+                    .clamp_range(f64::NEG_INFINITY..=self.max[0])
+                    .speed(0.1),
+            );
+            ui.add(
+                DragValue::new(&mut self.max[0])
+                    .clamp_range(self.min[0]..=f64::INFINITY)
+                    .speed(0.1),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Y");
+            ui.add(
+                DragValue::new(&mut self.min[1])
+                    .clamp_range(f64::NEG_INFINITY..=self.max[1])
+                    .speed(0.1),
+            );
+            ui.add(
+                DragValue::new(&mut self.max[1])
+                    .clamp_range(self.min[1]..=f64::INFINITY)
+                    .speed(0.1),
+            );
+        });
+
+        ui.label("Output");
+        ui.checkbox(&mut self.normalise, "Normalise (0..1 within bounds)");
+        ui.checkbox(&mut self.invert_y, "Invert Y");
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.output, PointOutput::Array, "[x, y]");
+            ui.radio_value(&mut self.output, PointOutput::Map, "{x, y}");
+        });
+
         common_save_button(ui, self, tether_agent);
     }
 
@@ -78,11 +304,21 @@ impl View for Point2DWidget {
             }
         }
 
+        if self.mode == PointInputMode::Drag {
+            let changed = self.render_draggable(ui);
+            if common_send_button(ui, self, true).clicked() || changed && self.common().auto_send {
+                self.send(tether_agent);
+            }
+            return;
+        }
+
+        let current = *self.value();
         let plot = Plot::new("tracking_plot")
             .width(PLOT_SIZE)
             .height(PLOT_SIZE)
             .data_aspect(1.0)
             .show(ui, |plot_ui| {
+                plot_ui.points(Points::new(vec![current]).radius(4.0));
                 (
                     plot_ui.screen_from_plot(PlotPoint::new(0.0, 0.0)),
                     plot_ui.pointer_coordinate(),
@@ -111,11 +347,8 @@ impl View for Point2DWidget {
             if let Some(c) = pointer_coordinate {
                 // println!("Pointer coordinates: {:?}", c)
                 let PlotPoint { x, y } = c;
-                let p = [x, y];
-                match tether_agent.encode_and_publish(&self.common().plug, p) {
-                    Ok(()) => debug!("Send OK"),
-                    Err(_) => error!("Failed to send; connected? {}", tether_agent.is_connected()),
-                }
+                *self.value_mut() = [x, y];
+                self.send(tether_agent);
             }
         }
     }
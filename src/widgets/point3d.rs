@@ -0,0 +1,214 @@
+use egui::{
+    plot::{Line, Plot, PlotPoint, PlotPoints, Points},
+    DragValue, Ui,
+};
+use serde::{Deserialize, Serialize};
+use tether_agent::TetherAgent;
+
+use crate::{
+    gui::widget_view::{
+        common_editable_values, common_in_use_heading, common_save_button, common_send,
+        common_send_button,
+    },
+    midi_mapping::MidiMapping,
+};
+
+use super::{Common, CustomWidget, View};
+
+type Point3D = [f64; 3];
+
+const AXIS_NAMES: [&str; 3] = ["X", "Y", "Z"];
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+/// Which pair of axes the plot shows (and dragging changes)
+pub enum PlotView {
+    XY,
+    XZ,
+    YZ,
+}
+
+impl PlotView {
+    /// Indexes of the horizontal and vertical axes
+    fn axes(&self) -> (usize, usize) {
+        match self {
+            PlotView::XY => (0, 1),
+            PlotView::XZ => (0, 2),
+            PlotView::YZ => (1, 2),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Publishes `[x, y, z]`, e.g. for simulating 3D tracking data
+pub struct Point3DWidget {
+    common: Common,
+    value: Point3D,
+    min: Point3D,
+    max: Point3D,
+    view: PlotView,
+}
+
+impl Point3DWidget {
+    pub fn new(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        agent: &mut TetherAgent,
+    ) -> Self {
+        Point3DWidget {
+            common: Common::new(widget_name, description, plug_name, custom_topic, agent),
+            value: [0., 0., 0.],
+            min: [-1., -1., -1.],
+            max: [1., 1., 1.],
+            view: PlotView::XY,
+        }
+    }
+
+    /// Min and max on each axis, swapped if necessary, e.g. for project
+    /// files edited by hand
+    fn bounds(&self) -> (Point3D, Point3D) {
+        let mut min = self.min;
+        let mut max = self.max;
+        for axis in 0..3 {
+            min[axis] = self.min[axis].min(self.max[axis]);
+            max[axis] = self.min[axis].max(self.max[axis]);
+        }
+        (min, max)
+    }
+}
+
+impl CustomWidget<Point3D> for Point3DWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &Point3D {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut Point3D {
+        &mut self.value
+    }
+}
+
+const PLOT_SIZE: f32 = 200.0;
+
+impl View for Point3DWidget {
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        common_editable_values(ui, self, tether_agent);
+
+        ui.label("Bounds");
+        egui::Grid::new(ui.id().with("bounds"))
+            .num_columns(3)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Min");
+                ui.label("Max");
+                ui.end_row();
+                for (axis, name) in AXIS_NAMES.iter().enumerate() {
+                    ui.label(*name);
+                    ui.add(
+                        DragValue::new(&mut self.min[axis])
+                            .clamp_range(f64::NEG_INFINITY..=self.max[axis])
+                            .speed(0.1),
+                    );
+                    ui.add(
+                        DragValue::new(&mut self.max[axis])
+                            .clamp_range(self.min[axis]..=f64::INFINITY)
+                            .speed(0.1),
+                    );
+                    ui.end_row();
+                }
+            });
+
+        common_save_button(ui, self, tether_agent);
+    }
+
+    fn render_in_use(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+
+        if let Some(midi) = &self.common().midi_mapping {
+            match midi {
+                MidiMapping::Learning => {}
+                MidiMapping::Set(mapping) => {
+                    ui.label(format!(
+                        "MIDI mapped: send on ch {} note {}",
+                        mapping.channel, mapping.controller_or_note
+                    ));
+                }
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.view, PlotView::XY, "XY");
+            ui.selectable_value(&mut self.view, PlotView::XZ, "XZ");
+            ui.selectable_value(&mut self.view, PlotView::YZ, "YZ");
+        });
+
+        let (h, v) = self.view.axes();
+        let current = [self.value[h], self.value[v]];
+        let (min, max) = self.bounds();
+        let bounds = vec![
+            [min[h], min[v]],
+            [max[h], min[v]],
+            [max[h], max[v]],
+            [min[h], max[v]],
+            [min[h], min[v]],
+        ];
+        let plot = Plot::new("point3d_plot")
+            .width(PLOT_SIZE)
+            .height(PLOT_SIZE)
+            .data_aspect(1.0)
+            .allow_drag(false)
+            .include_x(min[h])
+            .include_x(max[h])
+            .include_y(min[v])
+            .include_y(max[v])
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(bounds)));
+                plot_ui.points(Points::new(vec![current]).radius(4.0));
+                plot_ui.pointer_coordinate()
+            });
+        ui.small(format!(
+            "Drag to move in {}{}; {} is {:.3}",
+            AXIS_NAMES[h],
+            AXIS_NAMES[v],
+            AXIS_NAMES[3 - h - v],
+            self.value[3 - h - v]
+        ));
+
+        let mut changed = false;
+        if plot.response.dragged() || plot.response.clicked() {
+            if let Some(PlotPoint { x, y }) = plot.inner {
+                self.value[h] = x.clamp(min[h], max[h]);
+                self.value[v] = y.clamp(min[v], max[v]);
+                changed = true;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            for (axis, name) in AXIS_NAMES.iter().enumerate() {
+                ui.label(*name);
+                let speed = (max[axis] - min[axis]) / 200.;
+                if ui
+                    .add(
+                        DragValue::new(&mut self.value[axis])
+                            .clamp_range(min[axis]..=max[axis])
+                            .speed(speed),
+                    )
+                    .changed()
+                {
+                    changed = true;
+                }
+            }
+        });
+
+        if common_send_button(ui, self, true).clicked() || changed && self.common().auto_send {
+            common_send(self, tether_agent);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::ops::RangeInclusive;
+
+use egui::{emath::remap_clamp, pos2, vec2, DragValue, Pos2, Rect, Sense, Shape, Stroke, Ui};
+use serde::{Deserialize, Serialize};
+
+const EDITOR_SIZE: f32 = 160.0;
+/// How close (in screen pixels) the pointer must be to pick up a point
+const HIT_RADIUS: f32 = 8.0;
+/// For logarithmic ranges which include zero, the ratio between the largest
+/// and smallest step (i.e. 60 dB)
+const LOG_RATIO: f64 = 1000.;
+/// Custom curves are inverted by searching this many positions
+const INVERSE_STEPS: usize = 512;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum CurveKind {
+    Linear,
+    /// Equal ratios for equal movements, e.g. for frequency or gain
+    Logarithmic,
+    /// The position raised to a power; above 1 gives finer control near the
+    /// minimum
+    Exponential,
+    /// Straight lines between user-placed points
+    Custom,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// Maps a control position (0-1, e.g. a slider or MIDI CC) onto a range
+pub struct ResponseCurve {
+    pub kind: CurveKind,
+    pub exponent: f64,
+    /// `[position, output]` pairs, each 0-1, sorted by position; the first
+    /// and last are always at position 0 and 1
+    pub points: Vec<[f64; 2]>,
+}
+
+impl Default for ResponseCurve {
+    fn default() -> Self {
+        ResponseCurve {
+            kind: CurveKind::Linear,
+            exponent: 2.0,
+            points: vec![[0., 0.], [1., 1.]],
+        }
+    }
+}
+
+impl ResponseCurve {
+    pub fn is_linear(&self) -> bool {
+        self.kind == CurveKind::Linear
+    }
+
+    /// Output (0-1) of the curve for a position (0-1), ignoring the range
+    fn shape(&self, position: f64) -> f64 {
+        let position = position.clamp(0., 1.);
+        match self.kind {
+            CurveKind::Linear => position,
+            CurveKind::Logarithmic => (LOG_RATIO.powf(position) - 1.) / (LOG_RATIO - 1.),
+            CurveKind::Exponential => position.powf(self.exponent.max(f64::EPSILON)),
+            CurveKind::Custom => match self.points.iter().position(|[x, _]| *x >= position) {
+                None => self.points.last().map_or(position, |[_, y]| *y),
+                Some(0) => self.points[0][1],
+                Some(i) => {
+                    let ([x0, y0], [x1, y1]) = (self.points[i - 1], self.points[i]);
+                    if x1 <= x0 {
+                        y1
+                    } else {
+                        y0 + (y1 - y0) * (position - x0) / (x1 - x0)
+                    }
+                }
+            },
+        }
+    }
+
+    /// A true logarithmic mapping is only possible if the range does not
+    /// include (or cross) zero
+    fn is_log_range(&self, range: &RangeInclusive<f64>) -> bool {
+        self.kind == CurveKind::Logarithmic && range.start() * range.end() > 0.
+    }
+
+    pub fn to_value(&self, position: f64, range: RangeInclusive<f64>) -> f64 {
+        let (min, max) = (*range.start(), *range.end());
+        if self.is_log_range(&range) {
+            min * (max / min).powf(position.clamp(0., 1.))
+        } else {
+            min + (max - min) * self.shape(position)
+        }
+    }
+
+    pub fn to_position(&self, value: f64, range: RangeInclusive<f64>) -> f64 {
+        let (min, max) = (*range.start(), *range.end());
+        if self.is_log_range(&range) {
+            return ((value / min).ln() / (max / min).ln()).clamp(0., 1.);
+        }
+        let output = if max == min {
+            0.
+        } else {
+            ((value - min) / (max - min)).clamp(0., 1.)
+        };
+        match self.kind {
+            CurveKind::Linear => output,
+            CurveKind::Logarithmic => (1. + output * (LOG_RATIO - 1.)).ln() / LOG_RATIO.ln(),
+            CurveKind::Exponential => output.powf(1. / self.exponent.max(f64::EPSILON)),
+            // May not be one-to-one, so take the closest
+            CurveKind::Custom => (0..=INVERSE_STEPS)
+                .map(|i| i as f64 / INVERSE_STEPS as f64)
+                .min_by(|a, b| {
+                    (self.shape(*a) - output)
+                        .abs()
+                        .total_cmp(&(self.shape(*b) - output).abs())
+                })
+                .unwrap_or(output),
+        }
+    }
+
+    pub fn render_editor(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.radio_value(&mut self.kind, CurveKind::Linear, "Linear");
+            ui.radio_value(&mut self.kind, CurveKind::Logarithmic, "Log");
+            ui.radio_value(&mut self.kind, CurveKind::Exponential, "Exp");
+            ui.radio_value(&mut self.kind, CurveKind::Custom, "Custom");
+        });
+        if self.kind == CurveKind::Exponential {
+            ui.horizontal(|ui| {
+                ui.label("Exponent");
+                ui.add(
+                    DragValue::new(&mut self.exponent)
+                        .speed(0.05)
+                        .clamp_range(0.1..=10.),
+                );
+            });
+        }
+
+        let (rect, response) =
+            ui.allocate_exact_size(vec2(EDITOR_SIZE, EDITOR_SIZE), Sense::click_and_drag());
+        let to_screen = |[x, y]: [f64; 2]| {
+            pos2(
+                remap_clamp(x as f32, 0. ..=1., rect.x_range()),
+                remap_clamp(y as f32, 0. ..=1., rect.bottom()..=rect.top()),
+            )
+        };
+        let from_screen = |pos: Pos2| {
+            [
+                remap_clamp(pos.x, rect.x_range(), 0. ..=1.) as f64,
+                remap_clamp(pos.y, rect.bottom()..=rect.top(), 0. ..=1.) as f64,
+            ]
+        };
+
+        if self.kind == CurveKind::Custom {
+            self.edit_points(ui, rect, &response, to_screen, from_screen);
+        }
+
+        let visuals = ui.style().interact(&response);
+        let painter = ui.painter();
+        painter.rect(rect, 2.0, visuals.bg_fill, visuals.bg_stroke);
+        let line: Vec<Pos2> = (0..=64)
+            .map(|i| {
+                let x = i as f64 / 64.;
+                to_screen([x, self.shape(x)])
+            })
+            .collect();
+        painter.add(Shape::line(
+            line,
+            Stroke::new(2.0, ui.visuals().selection.bg_fill),
+        ));
+        if self.kind == CurveKind::Custom {
+            for point in &self.points {
+                painter.circle_filled(to_screen(*point), 4.0, visuals.fg_stroke.color);
+            }
+            ui.small("Click to add a point, drag to move, right-click to remove");
+        } else if self.kind == CurveKind::Logarithmic {
+            ui.small("Truly logarithmic if the range does not include zero");
+        }
+    }
+
+    fn edit_points(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        response: &egui::Response,
+        to_screen: impl Fn([f64; 2]) -> Pos2,
+        from_screen: impl Fn(Pos2) -> [f64; 2],
+    ) {
+        if self.points.len() < 2 {
+            self.points = ResponseCurve::default().points;
+        }
+        let pointer = match response.interact_pointer_pos().or(response.hover_pos()) {
+            Some(pos) => pos,
+            None => return,
+        };
+        let nearest = self
+            .points
+            .iter()
+            .map(|p| to_screen(*p).distance(pointer))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .filter(|(_, distance)| *distance < HIT_RADIUS)
+            .map(|(i, _)| i);
+        let drag_id = response.id.with("dragging");
+
+        if response.drag_started() {
+            ui.data_mut(|d| match nearest {
+                Some(i) => d.insert_temp(drag_id, i),
+                None => d.remove::<usize>(drag_id),
+            });
+        }
+        let dragging = ui.data_mut(|d| d.get_temp::<usize>(drag_id));
+        if response.dragged() {
+            if let Some(i) = dragging {
+                let [x, y] = from_screen(pointer);
+                let last = self.points.len() - 1;
+                let x = if i == 0 {
+                    0.
+                } else if i == last {
+                    1.
+                } else {
+                    x.clamp(self.points[i - 1][0], self.points[i + 1][0])
+                };
+                self.points[i] = [x, y];
+            }
+        } else if response.clicked() && nearest.is_none() && rect.contains(pointer) {
+            let point = from_screen(pointer);
+            let index = self
+                .points
+                .iter()
+                .position(|[x, _]| *x > point[0])
+                .unwrap_or(self.points.len());
+            self.points.insert(index, point);
+        } else if response.secondary_clicked() {
+            if let Some(i) = nearest {
+                // The end points are always kept
+                if i != 0 && i != self.points.len() - 1 {
+                    self.points.remove(i);
+                }
+            }
+        }
+        if response.drag_released() {
+            ui.data_mut(|d| d.remove::<usize>(drag_id));
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use egui::{Key, Modifiers, TextEdit, Ui};
+use serde::{Deserialize, Serialize};
+use tether_agent::TetherAgent;
+
+use crate::{
+    gui::widget_view::{
+        common_editable_values, common_in_use_heading, common_save_button, common_send,
+        common_send_button,
+    },
+    midi_mapping::MidiMapping,
+};
+
+use super::{Common, CustomWidget, View};
+
+/// Number of recently sent strings kept
+const HISTORY_LENGTH: usize = 10;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Sends a plain string (encoded as a MessagePack string)
+pub struct StringWidget {
+    common: Common,
+    value: String,
+    multi_line: bool,
+    /// Send when Enter is pressed (single line), or Ctrl+Enter (multi-line)
+    send_on_enter: bool,
+    /// Recently sent strings, most recent first
+    history: Vec<String>,
+    /// Predefined values, each sent with a single click
+    quick_picks: Vec<String>,
+}
+
+impl StringWidget {
+    pub fn new(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        agent: &mut TetherAgent,
+    ) -> Self {
+        StringWidget {
+            common: Common::new(widget_name, description, plug_name, custom_topic, agent),
+            value: String::new(),
+            multi_line: false,
+            send_on_enter: true,
+            history: Vec::new(),
+            quick_picks: Vec::new(),
+        }
+    }
+
+    /// Keep the text and recently sent strings of the same Widget from
+    /// before an undo or redo, which does not record them
+    pub fn keep_text(&mut self, previous: &mut StringWidget) {
+        std::mem::swap(&mut self.value, &mut previous.value);
+        std::mem::swap(&mut self.history, &mut previous.history);
+    }
+
+    /// Publish the current value, and remember it in the history
+    pub fn send(&mut self, tether_agent: &TetherAgent) {
+        common_send(self, tether_agent);
+        let sent = self.value.clone();
+        self.history.retain(|s| s != &sent);
+        self.history.insert(0, sent);
+        self.history.truncate(HISTORY_LENGTH);
+    }
+}
+
+impl CustomWidget<String> for StringWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &String {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut String {
+        &mut self.value
+    }
+}
+
+impl View for StringWidget {
+    fn render_in_use(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+
+        if let Some(midi) = &self.common().midi_mapping {
+            match midi {
+                MidiMapping::Learning => {}
+                MidiMapping::Set(mapping) => {
+                    ui.label(format!(
+                        "MIDI mapped: send on ch {} note {}",
+                        mapping.channel, mapping.controller_or_note
+                    ));
+                }
+            }
+        }
+
+        let mut should_send = false;
+
+        if self.multi_line {
+            let id = ui.make_persistent_id("string_value");
+            // Must be consumed before the text edit sees it, or it adds a newline
+            if self.send_on_enter
+                && ui.memory(|m| m.has_focus(id))
+                && ui.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::Enter))
+            {
+                should_send = true;
+            }
+            ui.add(TextEdit::multiline(&mut self.value).id(id));
+            if self.send_on_enter {
+                ui.small("Ctrl+Enter to send");
+            }
+        } else {
+            let response = ui.text_edit_singleline(&mut self.value);
+            if self.send_on_enter
+                && response.lost_focus()
+                && ui.input(|i| i.key_pressed(Key::Enter))
+            {
+                should_send = true;
+            }
+        }
+
+        let quick_picks: Vec<&String> = self.quick_picks.iter().filter(|s| !s.is_empty()).collect();
+        if !quick_picks.is_empty() {
+            let mut picked = None;
+            ui.horizontal_wrapped(|ui| {
+                for s in quick_picks {
+                    if ui.button(s).clicked() {
+                        picked = Some(s.clone());
+                    }
+                }
+            });
+            if let Some(s) = picked {
+                self.value = s;
+                should_send = true;
+            }
+        }
+
+        if !self.history.is_empty() {
+            let mut recalled = None;
+            ui.menu_button("Recent", |ui| {
+                for s in &self.history {
+                    if ui.button(s).on_hover_text("Edit again").clicked() {
+                        recalled = Some(s.clone());
+                        ui.close_menu();
+                    }
+                }
+            });
+            if let Some(s) = recalled {
+                self.value = s;
+            }
+        }
+
+        if common_send_button(ui, self, false).clicked() || should_send {
+            self.send(tether_agent);
+        }
+    }
+
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        common_editable_values(ui, self, tether_agent);
+
+        ui.checkbox(&mut self.multi_line, "Multi-line");
+        ui.checkbox(&mut self.send_on_enter, "Send on Enter")
+            .on_hover_text("Ctrl+Enter in multi-line mode");
+
+        ui.label("Quick picks (one per line)");
+        let mut text = self.quick_picks.join("\n");
+        if ui.text_edit_multiline(&mut text).changed() {
+            // Not `lines()`, which would drop a newline just typed
+            self.quick_picks = text.split('\n').map(String::from).collect();
+        }
+        if ui.button("Clear history").clicked() {
+            self.history.clear();
+        }
+
+        common_save_button(ui, self, tether_agent);
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    f64::consts::PI,
+    time::{Duration, Instant},
+};
+
+use egui::{
+    plot::{Line, Plot, PlotPoint, PlotPoints, Points, Text},
+    Color32, DragValue, Ui,
+};
+use serde::{Deserialize, Serialize};
+use tether_agent::TetherAgent;
+
+use crate::{
+    gui::widget_view::{
+        common_editable_values, common_in_use_heading, common_save_button, common_send,
+        common_send_button,
+    },
+    midi_mapping::MidiMapping,
+};
+
+use super::{rng::Rng, Common, CustomWidget, View};
+
+const PLOT_SIZE: f32 = 240.0;
+/// How close (in screen pixels) the pointer must be to pick up a point
+const HIT_RADIUS: f32 = 10.0;
+/// Maximum change of direction for a random walk, in radians per second
+const MAX_TURN_RATE: f64 = 6.0;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// One tracked point, published as `{id, x, y}`
+pub struct TrackedPoint {
+    pub id: u64,
+    pub x: f64,
+    pub y: f64,
+    /// Direction of travel, in radians; not published, so missing (and
+    /// chosen at random) for points loaded, pasted or restored by undo
+    #[serde(skip)]
+    heading: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+/// How points move on their own while the simulation is running
+pub enum Motion {
+    Still,
+    /// Wander, changing direction at random
+    RandomWalk,
+    /// Travel in straight lines, bouncing off the bounds
+    Bounce,
+    /// Orbit the centre of the bounds
+    Circle,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+/// Simulates a people-tracking Agent: publishes a set of points with IDs,
+/// i.e. `[{id, x, y}, ...]`, which can be dragged by hand or move on their own
+pub struct TrackingWidget {
+    common: Common,
+    value: Vec<TrackedPoint>,
+    min: [f64; 2],
+    max: [f64; 2],
+    motion: Motion,
+    /// Units per second
+    speed: f64,
+    publish_rate_hz: f64,
+
+    #[serde(skip)]
+    is_running: bool,
+    #[serde(skip)]
+    last_step: Option<Instant>,
+    #[serde(skip)]
+    last_publish: Option<Instant>,
+    /// Index of the point being dragged, if any
+    #[serde(skip)]
+    dragging: Option<usize>,
+    #[serde(skip, default = "Rng::from_time")]
+    rng: Rng,
+}
+
+impl TrackingWidget {
+    pub fn new(
+        widget_name: &str,
+        description: Option<&str>,
+        plug_name: &str,
+        custom_topic: Option<&str>,
+        agent: &mut TetherAgent,
+    ) -> Self {
+        let mut widget = TrackingWidget {
+            common: Common::new(widget_name, description, plug_name, custom_topic, agent),
+            value: Vec::new(),
+            min: [0., 0.],
+            max: [1., 1.],
+            motion: Motion::RandomWalk,
+            speed: 0.2,
+            publish_rate_hz: 10.,
+            is_running: false,
+            last_step: None,
+            last_publish: None,
+            dragging: None,
+            rng: Rng::from_time(),
+        };
+        widget.add_point(0.5, 0.5);
+        widget
+    }
+
+    fn add_point(&mut self, x: f64, y: f64) {
+        let id = self.value.iter().map(|p| p.id + 1).max().unwrap_or(0);
+        let heading = Some(self.rng.next_f64() * 2. * PI);
+        self.value.push(TrackedPoint { id, x, y, heading });
+    }
+
+    /// Min and max on each axis, swapped if necessary, e.g. for project
+    /// files edited by hand
+    fn bounds(&self) -> ([f64; 2], [f64; 2]) {
+        (
+            [self.min[0].min(self.max[0]), self.min[1].min(self.max[1])],
+            [self.min[0].max(self.max[0]), self.min[1].max(self.max[1])],
+        )
+    }
+
+    fn publish_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.publish_rate_hz.max(0.1))
+    }
+
+    /// Move the points and publish at the fixed rate, while running. Called
+    /// every frame whether or not the Widget is shown; returns the time until
+    /// the next update is due.
+    pub fn update(&mut self, tether_agent: &TetherAgent) -> Option<Duration> {
+        if !self.is_running {
+            self.last_step = None;
+            return None;
+        }
+        let now = Instant::now();
+        let dt = self
+            .last_step
+            .map(|t| (now - t).as_secs_f64())
+            .unwrap_or(0.);
+        self.last_step = Some(now);
+        self.move_points(dt);
+
+        let interval = self.publish_interval();
+        if self
+            .last_publish
+            .map(|t| t.elapsed() >= interval)
+            .unwrap_or(true)
+        {
+            common_send(self, tether_agent);
+            self.last_publish = Some(now);
+        }
+        Some(interval.saturating_sub(now.elapsed()))
+    }
+
+    fn move_points(&mut self, dt: f64) {
+        let (min, max) = self.bounds();
+        let centre = [(min[0] + max[0]) / 2., (min[1] + max[1]) / 2.];
+        let distance = self.speed * dt;
+        for (i, p) in self.value.iter_mut().enumerate() {
+            if self.dragging == Some(i) {
+                continue;
+            }
+            match self.motion {
+                Motion::Still => {}
+                Motion::RandomWalk | Motion::Bounce => {
+                    let mut heading = match p.heading {
+                        Some(heading) => heading,
+                        None => self.rng.next_f64() * 2. * PI,
+                    };
+                    if self.motion == Motion::RandomWalk {
+                        heading += (self.rng.next_f64() - 0.5) * 2. * MAX_TURN_RATE * dt;
+                    }
+                    p.x += heading.cos() * distance;
+                    p.y += heading.sin() * distance;
+                    if p.x < min[0] || p.x > max[0] {
+                        heading = PI - heading;
+                    }
+                    if p.y < min[1] || p.y > max[1] {
+                        heading = -heading;
+                    }
+                    p.heading = Some(heading);
+                }
+                Motion::Circle => {
+                    let (dx, dy) = (p.x - centre[0], p.y - centre[1]);
+                    let radius = dx.hypot(dy).max(0.01);
+                    let angle = dy.atan2(dx) + distance / radius;
+                    p.x = centre[0] + radius * angle.cos();
+                    p.y = centre[1] + radius * angle.sin();
+                }
+            }
+            p.x = p.x.clamp(min[0], max[0]);
+            p.y = p.y.clamp(min[1], max[1]);
+        }
+    }
+}
+
+/// IDs must be unique, so a change to one already taken skips past it, in the
+/// direction of the change; `None` if there is no free ID that way
+fn free_id(taken: &[u64], id: u64, is_increase: bool) -> Option<u64> {
+    if is_increase {
+        (id..).find(|candidate| !taken.contains(candidate))
+    } else {
+        (0..=id).rev().find(|candidate| !taken.contains(candidate))
+    }
+}
+
+impl CustomWidget<Vec<TrackedPoint>> for TrackingWidget {
+    fn common(&self) -> &Common {
+        &self.common
+    }
+    fn common_mut(&mut self) -> &mut Common {
+        &mut self.common
+    }
+    fn value(&self) -> &Vec<TrackedPoint> {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut Vec<TrackedPoint> {
+        &mut self.value
+    }
+}
+
+impl View for TrackingWidget {
+    fn render_editing(&mut self, ui: &mut Ui, tether_agent: &mut TetherAgent) {
+        common_editable_values(ui, self, tether_agent);
+
+        ui.label("Bounds");
+        ui.horizontal(|ui| {
+            ui.label("X");
+            ui.add(
+                DragValue::new(&mut self.min[0])
+                    .clamp_range(f64::NEG_INFINITY..=self.max[0])
+                    .speed(0.1),
+            );
+            ui.add(
+                DragValue::new(&mut self.max[0])
+                    .clamp_range(self.min[0]..=f64::INFINITY)
+                    .speed(0.1),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Y");
+            ui.add(
+                DragValue::new(&mut self.min[1])
+                    .clamp_range(f64::NEG_INFINITY..=self.max[1])
+                    .speed(0.1),
+            );
+            ui.add(
+                DragValue::new(&mut self.max[1])
+                    .clamp_range(self.min[1]..=f64::INFINITY)
+                    .speed(0.1),
+            );
+        });
+
+        common_save_button(ui, self, tether_agent);
+    }
+
+    fn render_in_use(&mut self, ui: &mut Ui, tether_agent: &TetherAgent) {
+        common_in_use_heading(ui, self);
+
+        if let Some(midi) = &self.common().midi_mapping {
+            match midi {
+                MidiMapping::Learning => {}
+                MidiMapping::Set(mapping) => {
+                    ui.label(format!(
+                        "MIDI mapped: send on ch {} note {}",
+                        mapping.channel, mapping.controller_or_note
+                    ));
+                }
+            }
+        }
+
+        let (min, max) = self.bounds();
+        let bounds = vec![
+            [min[0], min[1]],
+            [max[0], min[1]],
+            [max[0], max[1]],
+            [min[0], max[1]],
+            [min[0], min[1]],
+        ];
+        let pointer_pos = ui.ctx().input(|i| i.pointer.hover_pos());
+        let points = &self.value;
+        let plot = Plot::new("tracking_points")
+            .width(PLOT_SIZE)
+            .height(PLOT_SIZE)
+            .data_aspect(1.0)
+            .allow_drag(false)
+            .include_x(min[0])
+            .include_x(max[0])
+            .include_y(min[1])
+            .include_y(max[1])
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(bounds)).color(Color32::DARK_GRAY));
+                plot_ui.points(
+                    Points::new(points.iter().map(|p| [p.x, p.y]).collect::<Vec<_>>()).radius(5.0),
+                );
+                for p in points {
+                    plot_ui.text(
+                        Text::new(PlotPoint::new(p.x, p.y), format!("  {}", p.id))
+                            .anchor(egui::Align2::LEFT_BOTTOM),
+                    );
+                }
+                let hit = pointer_pos.and_then(|pos| {
+                    points.iter().position(|p| {
+                        plot_ui
+                            .screen_from_plot(PlotPoint::new(p.x, p.y))
+                            .distance(pos)
+                            < HIT_RADIUS
+                    })
+                });
+                (hit, plot_ui.pointer_coordinate())
+            });
+        let (hit, pointer_coordinate) = plot.inner;
+
+        let mut changed = false;
+        if plot.response.drag_started() {
+            self.dragging = hit;
+        }
+        if let (Some(i), Some(PlotPoint { x, y })) = (self.dragging, pointer_coordinate) {
+            if let Some(p) = self.value.get_mut(i) {
+                p.x = x.clamp(min[0], max[0]);
+                p.y = y.clamp(min[1], max[1]);
+                changed = true;
+            }
+        }
+        if plot.response.drag_released() {
+            self.dragging = None;
+        }
+        if plot.response.clicked() && hit.is_none() {
+            if let Some(PlotPoint { x, y }) = pointer_coordinate {
+                self.add_point(x.clamp(min[0], max[0]), y.clamp(min[1], max[1]));
+                changed = true;
+            }
+        }
+        ui.small("Click to add a point, drag to move one");
+
+        ui.collapsing(format!("Points ({})", self.value.len()), |ui| {
+            let mut to_remove = None;
+            let ids: Vec<u64> = self.value.iter().map(|p| p.id).collect();
+            egui::Grid::new(ui.id().with("points"))
+                .num_columns(4)
+                .show(ui, |ui| {
+                    ui.label("ID");
+                    ui.label("X");
+                    ui.label("Y");
+                    ui.end_row();
+                    for (i, p) in self.value.iter_mut().enumerate() {
+                        let mut id = p.id;
+                        if ui.add(DragValue::new(&mut id)).changed() {
+                            let others: Vec<u64> = ids
+                                .iter()
+                                .enumerate()
+                                .filter(|(j, _)| *j != i)
+                                .map(|(_, other)| *other)
+                                .collect();
+                            if let Some(id) = free_id(&others, id, id > p.id) {
+                                p.id = id;
+                                changed = true;
+                            }
+                        }
+                        changed |= ui
+                            .add(
+                                DragValue::new(&mut p.x)
+                                    .clamp_range(min[0]..=max[0])
+                                    .speed(0.01),
+                            )
+                            .changed();
+                        changed |= ui
+                            .add(
+                                DragValue::new(&mut p.y)
+                                    .clamp_range(min[1]..=max[1])
+                                    .speed(0.01),
+                            )
+                            .changed();
+                        if ui.button("❌").clicked() {
+                            to_remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(i) = to_remove {
+                self.value.remove(i);
+                changed = true;
+            }
+            let mut sorted = ids;
+            sorted.sort_unstable();
+            if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+                ui.colored_label(Color32::RED, "Some points share the same ID");
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Motion");
+            egui::ComboBox::from_id_source(ui.id().with("motion"))
+                .selected_text(format!("{:?}", self.motion))
+                .show_ui(ui, |ui| {
+                    for motion in [
+                        Motion::Still,
+                        Motion::RandomWalk,
+                        Motion::Bounce,
+                        Motion::Circle,
+                    ] {
+                        ui.selectable_value(&mut self.motion, motion, format!("{:?}", motion));
+                    }
+                });
+            ui.label("Speed");
+            ui.add(
+                DragValue::new(&mut self.speed)
+                    .speed(0.01)
+                    .clamp_range(0.0..=100.0),
+            );
+        });
+        ui.horizontal(|ui| {
+            if self.is_running {
+                if ui.button("⏹ Stop").clicked() {
+                    self.is_running = false;
+                }
+            } else if ui.button("▶ Start").clicked() {
+                self.is_running = true;
+            }
+            ui.label("Publish rate (Hz)");
+            ui.add(
+                DragValue::new(&mut self.publish_rate_hz)
+                    .clamp_range(0.1..=120.0)
+                    .speed(0.1),
+            );
+        });
+
+        if common_send_button(ui, self, true).clicked()
+            || changed && !self.is_running && self.common().auto_send
+        {
+            common_send(self, tether_agent);
+        }
+    }
+}